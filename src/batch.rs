@@ -0,0 +1,79 @@
+// Background export of a run of consecutive seeds
+
+use crate::export::save_png;
+use crate::terrain::{generate_terrain, TerrainConfig};
+use crate::SavedConfig;
+use eframe::egui;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+pub struct BatchJob {
+    count: u32,
+    done: Arc<AtomicU32>,
+    cancel: Arc<AtomicBool>,
+    // Sends the number of maps written, or the first error, once the worker stops
+    receiver: Receiver<Result<u32, String>>,
+}
+
+impl BatchJob {
+    // Write terrain_<seed>.png into `dir` for `count` seeds starting at `start`, wrapping past u32::MAX
+    pub fn spawn(ctx: &egui::Context, config: TerrainConfig, start: u32, count: u32, dir: PathBuf) -> Self {
+        let done = Arc::new(AtomicU32::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let (ctx, worker_done, worker_cancel) = (ctx.clone(), Arc::clone(&done), Arc::clone(&cancel));
+        thread::spawn(move || {
+            let result = export_range(&ctx, &config, start, count, &dir, &worker_done, &worker_cancel);
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        Self { count, done, cancel, receiver }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.done.load(Ordering::Relaxed) as f32 / self.count.max(1) as f32
+    }
+
+    // Stop after the map currently being generated
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    // The outcome once the worker has stopped
+    pub fn poll(&self) -> Option<Result<u32, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("batch export stopped unexpectedly".to_owned())),
+        }
+    }
+}
+
+fn export_range(
+    ctx: &egui::Context,
+    config: &TerrainConfig,
+    start: u32,
+    count: u32,
+    dir: &Path,
+    done: &AtomicU32,
+    cancel: &AtomicBool,
+) -> Result<u32, String> {
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    for offset in 0..count {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let seed = start.wrapping_add(offset);
+        let path = dir.join(format!("terrain_{}.png", seed));
+        let terrain = generate_terrain(config, seed);
+        let parameters = SavedConfig::embedded(config, seed);
+        save_png(&terrain.image, Some(&parameters), &path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        done.fetch_add(1, Ordering::Relaxed);
+        ctx.request_repaint();
+    }
+    Ok(done.load(Ordering::Relaxed))
+}
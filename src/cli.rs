@@ -0,0 +1,99 @@
+// Headless batch mode: generate a map from command-line options and save it without opening a window
+
+use crate::export::save_png;
+use crate::terrain::{generate_terrain, TerrainConfig};
+use crate::{limit_config, new_seed, SavedConfig};
+use std::fs;
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: terrain_generator --headless [--config <file.json>] [--seed <u32>] \
+[--width <px>] [--height <px>] [--out <file.png>]";
+
+struct HeadlessOptions {
+    config: TerrainConfig,
+    seed: u32,
+    out: Option<PathBuf>,
+}
+
+pub fn is_headless(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "--headless")
+}
+
+// Generate and save one map; returns the path written
+pub fn run(args: &[String]) -> Result<PathBuf, String> {
+    let options = parse(args)?;
+    let terrain = generate_terrain(&options.config, options.seed);
+    let out = options
+        .out
+        .unwrap_or_else(|| PathBuf::from(format!("terrain_{}.png", options.seed)));
+    let parameters = SavedConfig::embedded(&options.config, options.seed);
+    save_png(&terrain.image, Some(&parameters), &out).map_err(|err| format!("{}: {}", out.display(), err))?;
+    Ok(out)
+}
+
+fn parse(args: &[String]) -> Result<HeadlessOptions, String> {
+    let mut config = TerrainConfig::default();
+    let mut seed = None;
+    let mut width = None;
+    let mut height = None;
+    let mut out = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE));
+        match arg.as_str() {
+            "--headless" => {}
+            "--config" => {
+                let path = value()?;
+                let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+                let saved: SavedConfig = serde_json::from_str(&text).map_err(|err| format!("{}: {}", path, err))?;
+                config = saved.config;
+                config.sort_bands();
+                seed = seed.or(saved.seed);
+            }
+            "--seed" => seed = Some(parse_number(arg, value()?)?),
+            "--width" => width = Some(parse_number(arg, value()?)?),
+            "--height" => height = Some(parse_number(arg, value()?)?),
+            "--out" => out = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+        }
+    }
+
+    // Explicit sizes win over the config file
+    if let Some(width) = width {
+        config.width = width;
+    }
+    if let Some(height) = height {
+        config.height = height;
+    }
+    // The same limits as the GUI, but reported rather than quietly applied
+    let problems = limit_config(&mut config);
+    if !problems.is_empty() {
+        return Err(format!("Out of range: {}", problems.join("; ")));
+    }
+
+    Ok(HeadlessOptions {
+        config,
+        seed: seed.unwrap_or_else(new_seed),
+        out,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got {:?}", option, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_outside_the_limits_are_rejected() {
+        let args = |width: &str| ["--headless", "--width", width].map(str::to_owned);
+        assert!(parse(&args("100000")).is_err());
+        assert!(parse(&args("1")).is_err());
+        assert!(parse(&args("512")).is_ok());
+    }
+}
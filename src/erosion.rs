@@ -0,0 +1,202 @@
+// Erosion passes that reshape a generated height buffer
+
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+// Neighbor offsets and their distance in pixels
+pub const NEIGHBORS: [(isize, isize, f64); 8] = [
+    (-1, -1, std::f64::consts::SQRT_2),
+    (0, -1, 1.0),
+    (1, -1, std::f64::consts::SQRT_2),
+    (-1, 0, 1.0),
+    (1, 0, 1.0),
+    (-1, 1, std::f64::consts::SQRT_2),
+    (0, 1, 1.0),
+    (1, 1, std::f64::consts::SQRT_2),
+];
+
+// Material a cell sheds in one iteration and the sum of the drops it is shared out by
+#[derive(Clone, Copy, Default)]
+struct Outflow {
+    amount: f64,
+    total_drop: f64,
+}
+
+// Thermal weathering: wherever the drop to a neighbor exceeds `talus` per pixel of distance, half of
+// the steepest excess slides downhill, split between the too-steep neighbors in proportion to their
+// drop. Each iteration is computed from the previous one's heights, so rows can be processed in
+// parallel and the result doesn't depend on the traversal order. Stops early once `on_iteration`
+// returns false.
+pub fn thermal(heights: &mut [f64], size: [usize; 2], iterations: u32, talus: f64, mut on_iteration: impl FnMut() -> bool) {
+    let [width, height] = size;
+    let neighbor = |x: usize, y: usize, dx: isize, dy: isize| {
+        let (nx, ny) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        (nx < width && ny < height).then_some(ny * width + nx)
+    };
+    let mut outflow = vec![Outflow::default(); heights.len()];
+    let mut next = vec![0.0; heights.len()];
+
+    for _ in 0..iterations {
+        let current = &*heights;
+        outflow.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                let here = current[y * width + x];
+                let (mut steepest, mut total_drop) = (0.0f64, 0.0);
+                for (dx, dy, distance) in NEIGHBORS {
+                    if let Some(index) = neighbor(x, y, dx, dy) {
+                        let drop = here - current[index];
+                        if drop > talus * distance {
+                            steepest = steepest.max(drop - talus * distance);
+                            total_drop += drop;
+                        }
+                    }
+                }
+                *out = Outflow { amount: steepest * 0.5, total_drop };
+            }
+        });
+
+        let outflow = &outflow;
+        next.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            for (x, value) in row.iter_mut().enumerate() {
+                let here = current[y * width + x];
+                let mut received = 0.0;
+                for (dx, dy, distance) in NEIGHBORS {
+                    if let Some(index) = neighbor(x, y, dx, dy) {
+                        let drop = current[index] - here;
+                        let source = outflow[index];
+                        if drop > talus * distance && source.total_drop > 0.0 {
+                            received += source.amount * drop / source.total_drop;
+                        }
+                    }
+                }
+                *value = here - outflow[y * width + x].amount + received;
+            }
+        });
+        heights.copy_from_slice(&next);
+        if !on_iteration() {
+            return;
+        }
+    }
+}
+
+// Droplet simulation constants: how much a droplet keeps its direction, how much sediment it can carry
+// per unit of speed, water and slope, and how many steps it lives
+const INERTIA: f64 = 0.05;
+const CAPACITY_FACTOR: f64 = 4.0;
+const MIN_CAPACITY: f64 = 0.01;
+const GRAVITY: f64 = 4.0;
+const DROPLET_LIFETIME: usize = 30;
+
+// Droplets simulated between progress callbacks
+pub const DROPLET_BATCH: u32 = 1000;
+
+pub struct HydraulicParams {
+    pub droplets: u32,
+    pub erosion_rate: f64,
+    pub deposition_rate: f64,
+    pub evaporation: f64,
+}
+
+// Hydraulic erosion: each droplet starts at a random position and rolls downhill, picking up sediment
+// while it is faster than its load allows and dropping it once it slows down or climbs. Droplets run
+// one after another because each one reshapes the terrain the next one flows over. `on_batch` sees the
+// heights after every DROPLET_BATCH droplets and after the last one; the simulation stops early once it returns false.
+pub fn hydraulic(heights: &mut [f64], size: [usize; 2], params: &HydraulicParams, seed: u32, mut on_batch: impl FnMut(&[f64]) -> bool) {
+    let [width, height] = size;
+    if width < 2 || height < 2 {
+        return;
+    }
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+    let limit = [(width - 1) as f64, (height - 1) as f64];
+
+    for droplet in 0..params.droplets {
+        let mut position = [rng.gen_range(0.0..limit[0]), rng.gen_range(0.0..limit[1])];
+        let mut direction = [0.0, 0.0];
+        let (mut speed, mut water, mut sediment) = (1.0, 1.0, 0.0);
+
+        for _ in 0..DROPLET_LIFETIME {
+            let cell = [position[0] as usize, position[1] as usize];
+            let offset = [position[0] - cell[0] as f64, position[1] - cell[1] as f64];
+            let (here, gradient) = sample(heights, width, cell, offset);
+
+            direction = [
+                direction[0] * INERTIA - gradient[0] * (1.0 - INERTIA),
+                direction[1] * INERTIA - gradient[1] * (1.0 - INERTIA),
+            ];
+            let length = direction[0].hypot(direction[1]);
+            if length <= f64::EPSILON {
+                break;
+            }
+            direction = [direction[0] / length, direction[1] / length];
+            position = [position[0] + direction[0], position[1] + direction[1]];
+            if !(0.0..limit[0]).contains(&position[0]) || !(0.0..limit[1]).contains(&position[1]) {
+                break;
+            }
+
+            let next_cell = [position[0] as usize, position[1] as usize];
+            let next_offset = [position[0] - next_cell[0] as f64, position[1] - next_cell[1] as f64];
+            let delta = sample(heights, width, next_cell, next_offset).0 - here;
+            let capacity = (-delta * speed * water * CAPACITY_FACTOR).max(MIN_CAPACITY);
+
+            if sediment > capacity || delta > 0.0 {
+                // Going uphill fills the pit behind the droplet; otherwise drop the excess load
+                let deposit = if delta > 0.0 { delta.min(sediment) } else { (sediment - capacity) * params.deposition_rate };
+                sediment -= deposit;
+                spread(heights, width, cell, offset, deposit);
+            } else {
+                // Never dig deeper than the height difference, so erosion can't carve holes
+                let erode = ((capacity - sediment) * params.erosion_rate).min(-delta);
+                sediment += erode;
+                spread(heights, width, cell, offset, -erode);
+            }
+
+            speed = (speed * speed + delta * GRAVITY).max(0.0).sqrt();
+            water *= 1.0 - params.evaporation;
+        }
+
+        if (droplet + 1) % DROPLET_BATCH == 0 && !on_batch(heights) {
+            return;
+        }
+    }
+    // Report the last partial batch, so the step count reaches the total
+    if !params.droplets.is_multiple_of(DROPLET_BATCH) {
+        on_batch(heights);
+    }
+}
+
+// Bilinear height and gradient inside the cell whose top-left corner is `cell`
+fn sample(heights: &[f64], width: usize, cell: [usize; 2], offset: [f64; 2]) -> (f64, [f64; 2]) {
+    let index = cell[1] * width + cell[0];
+    let (nw, ne, sw, se) = (heights[index], heights[index + 1], heights[index + width], heights[index + width + 1]);
+    let [u, v] = offset;
+    let gradient = [(ne - nw) * (1.0 - v) + (se - sw) * v, (sw - nw) * (1.0 - u) + (se - ne) * u];
+    let height = nw * (1.0 - u) * (1.0 - v) + ne * u * (1.0 - v) + sw * (1.0 - u) * v + se * u * v;
+    (height, gradient)
+}
+
+// Add `amount` to the four corners of a cell, weighted by how close the droplet is to each
+fn spread(heights: &mut [f64], width: usize, cell: [usize; 2], offset: [f64; 2], amount: f64) {
+    let index = cell[1] * width + cell[0];
+    let [u, v] = offset;
+    heights[index] += amount * (1.0 - u) * (1.0 - v);
+    heights[index + 1] += amount * u * (1.0 - v);
+    heights[index + width] += amount * (1.0 - u) * v;
+    heights[index + width + 1] += amount * u * v;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_partial_last_batch_is_reported() {
+        let params = HydraulicParams { droplets: DROPLET_BATCH * 2 + 1, erosion_rate: 0.3, deposition_rate: 0.3, evaporation: 0.02 };
+        let mut heights = vec![0.5; 32 * 32];
+        let mut batches = 0;
+        hydraulic(&mut heights, [32, 32], &params, 1, |_| {
+            batches += 1;
+            true
+        });
+        assert_eq!(batches, params.droplets.div_ceil(DROPLET_BATCH));
+    }
+}
@@ -0,0 +1,165 @@
+// Writers for the generated terrain, shared by the GUI and the headless CLI
+
+use crate::terrain::{Heightmap, TerrainMaps};
+use eframe::egui;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// Height of the full 0..1 range in exported meshes relative to the map's longer side at exaggeration 1,
+// the same as the 3D view
+const MESH_RELIEF: f64 = 0.2;
+
+// iTXt keyword under which color exports carry the parameters that produced them
+const PARAMETERS_KEYWORD: &str = "terrain_generator";
+
+// Write a color image at its full resolution with a fully opaque alpha channel. `parameters` (the
+// config and seed as JSON) is embedded as an iTXt chunk so the map can be regenerated from the file.
+pub fn save_png(terrain: &egui::ColorImage, parameters: Option<&str>, path: &Path) -> image::ImageResult<()> {
+    let [width, height] = terrain.size;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(parameters) = parameters {
+        encoder.add_itxt_chunk(PARAMETERS_KEYWORD.to_owned(), parameters.to_owned()).map_err(encoding_error)?;
+    }
+    let mut writer = encoder.write_header().map_err(encoding_error)?;
+    let data: Vec<u8> = terrain.pixels.iter().flat_map(|c| [c.r(), c.g(), c.b(), 255]).collect();
+    writer.write_image_data(&data).map_err(encoding_error)?;
+    writer.finish().map_err(encoding_error)
+}
+
+// Read any image for viewing, along with the parameters embedded by `save_png` if it has them
+pub fn load_png(path: &Path) -> image::ImageResult<(egui::ColorImage, Option<String>)> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let parameters = decoder.read_info().ok().and_then(|reader| {
+        let info = reader.info();
+        let itxt = info.utf8_text.iter().find(|chunk| chunk.keyword == PARAMETERS_KEYWORD);
+        itxt.and_then(|chunk| chunk.get_text().ok())
+    });
+    let image = image::open(path)?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok((egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()), parameters))
+}
+
+// Slice an image into `tile_size` squares written as tile_<column>_<row>.png, plus a tiles.json manifest
+// of the grid. Tiles in the last column and row are clipped to the image rather than padded, and the
+// manifest records every tile's actual size. Returns the number of tiles written.
+pub fn save_tiles(terrain: &egui::ColorImage, tile_size: usize, dir: &Path) -> image::ImageResult<usize> {
+    let [width, height] = terrain.size;
+    let tile_size = tile_size.max(1);
+    let (columns, rows) = (width.div_ceil(tile_size), height.div_ceil(tile_size));
+    let mut tiles = Vec::with_capacity(columns * rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = (column * tile_size, row * tile_size);
+            let size = [tile_size.min(width - x), tile_size.min(height - y)];
+            let pixels = (0..size[1]).flat_map(|dy| terrain.pixels[(y + dy) * width + x..][..size[0]].iter().copied()).collect();
+            let file = format!("tile_{}_{}.png", column, row);
+            save_png(&egui::ColorImage { size, pixels }, None, &dir.join(&file))?;
+            tiles.push(serde_json::json!({ "file": file, "column": column, "row": row, "x": x, "y": y, "width": size[0], "height": size[1] }));
+        }
+    }
+    let manifest = serde_json::json!({
+        "width": width,
+        "height": height,
+        "tile_size": tile_size,
+        "columns": columns,
+        "rows": rows,
+        "tiles": tiles,
+    });
+    let text = serde_json::to_string_pretty(&manifest).expect("manifests always serialize");
+    std::fs::write(dir.join("tiles.json"), text)?;
+    Ok(tiles.len())
+}
+
+fn encoding_error(err: png::EncodingError) -> image::ImageError {
+    match err {
+        png::EncodingError::IoError(err) => image::ImageError::IoError(err),
+        err => image::ImageError::Encoding(image::error::EncodingError::new(image::ImageFormat::Png.into(), err)),
+    }
+}
+
+// Write the heights as 16-bit grayscale, mapping 0.0..=1.0 linearly onto 0..=65535
+pub fn save_heightmap(maps: &TerrainMaps, path: &Path) -> image::ImageResult<()> {
+    let [width, height] = maps.size;
+    let image: image::ImageBuffer<image::Luma<u16>, Vec<u16>> =
+        image::ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
+            let h = maps.heights[y as usize * width + x as usize].clamp(0.0, 1.0);
+            image::Luma([(h * u16::MAX as f64).round() as u16])
+        });
+    image.save_with_format(path, image::ImageFormat::Png)
+}
+
+// Read any image as heights, black at 0 and white at 1. Color images are converted to gray, and 16-bit
+// grayscale keeps its full precision, so a Save Heightmap export comes back as it was written.
+pub fn load_heightmap(path: &Path) -> image::ImageResult<Heightmap> {
+    let image = image::open(path)?.into_luma16();
+    let size = [image.width() as usize, image.height() as usize];
+    let heights = image.pixels().map(|pixel| pixel[0] as f64 / u16::MAX as f64).collect();
+    Ok(Heightmap { size, heights })
+}
+
+// Write a tangent-space normal map, each component mapped from -1..1 onto 0..255. Green points up the
+// image (the OpenGL convention) and blue out of the surface
+pub fn save_normal_map(maps: &TerrainMaps, strength: f64, path: &Path) -> image::ImageResult<()> {
+    let [width, height] = maps.size;
+    let encode = |component: f64| ((component * 0.5 + 0.5) * 255.0).round() as u8;
+    let image = image::RgbImage::from_fn(width as u32, height as u32, |x, y| {
+        let [nx, ny, nz] = maps.normal(x as usize, y as usize, strength);
+        image::Rgb([encode(nx), encode(-ny), encode(nz)])
+    });
+    image.save_with_format(path, image::ImageFormat::Png)
+}
+
+// Write the heights as a triangulated Wavefront OBJ mesh with a vertex every `stride` pixels (and always
+// on the last row and column), plus a material library naming `texture` as the color map. The mesh is Y
+// up, the map's longer side spans one unit, and UVs map each vertex onto its pixel of the color image.
+pub fn save_obj(maps: &TerrainMaps, stride: usize, exaggeration: f64, texture: &str, path: &Path) -> io::Result<()> {
+    let [width, height] = maps.size;
+    if width < 2 || height < 2 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the map is too small for a mesh"));
+    }
+    let stride = stride.max(1);
+    let samples = |extent: usize| (0..extent).step_by(stride).chain((!(extent - 1).is_multiple_of(stride)).then_some(extent - 1)).collect::<Vec<_>>();
+    let (xs, ys) = (samples(width), samples(height));
+    let longest = width.max(height) as f64;
+
+    let material = path.with_extension("mtl");
+    let material_name = material.file_name().map_or_else(|| "terrain.mtl".into(), |name| name.to_string_lossy());
+    std::fs::write(&material, format!("newmtl terrain\nKd 1 1 1\nmap_Kd {}\n", texture))?;
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "mtllib {}", material_name)?;
+    for &y in &ys {
+        for &x in &xs {
+            let elevation = maps.heights[y * width + x] * MESH_RELIEF * exaggeration;
+            writeln!(out, "v {:.6} {:.6} {:.6}", x as f64 / longest, elevation, y as f64 / longest)?;
+        }
+    }
+    for &y in &ys {
+        for &x in &xs {
+            let (u, v) = ((x as f64 + 0.5) / width as f64, 1.0 - (y as f64 + 0.5) / height as f64);
+            writeln!(out, "vt {:.6} {:.6}", u, v)?;
+        }
+    }
+    writeln!(out, "usemtl terrain")?;
+    // OBJ indices start at 1; vertex and UV share them. Counter-clockwise seen from above.
+    let columns = xs.len();
+    for row in 0..ys.len() - 1 {
+        for column in 0..columns - 1 {
+            let corner = |dx: usize, dy: usize| (row + dy) * columns + column + dx + 1;
+            let (a, b, c, d) = (corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1));
+            writeln!(out, "f {a}/{a} {d}/{d} {c}/{c}")?;
+            writeln!(out, "f {a}/{a} {c}/{c} {b}/{b}")?;
+        }
+    }
+    out.flush()
+}
+
+// Write scattered points as JSON, with the settings that placed them
+pub fn save_points(points: &[[usize; 2]], biome: &str, spacing: f64, seed: u32, path: &Path) -> io::Result<()> {
+    let points: Vec<_> = points.iter().map(|&[x, y]| serde_json::json!({ "x": x, "y": y })).collect();
+    let document = serde_json::json!({ "biome": biome, "spacing": spacing, "seed": seed, "points": points });
+    std::fs::write(path, serde_json::to_string_pretty(&document)?)
+}
@@ -0,0 +1,70 @@
+// Small previews of several seeds with the current parameters, generated in the background
+
+use crate::terrain::{generate_terrain, TerrainConfig};
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+// Longer side of each preview, in pixels
+const THUMBNAIL_SIZE: u32 = 96;
+
+pub struct Gallery {
+    pub seeds: Vec<u32>,
+    // One texture per seed once its preview has arrived
+    pub textures: Vec<Option<egui::TextureHandle>>,
+    cancel: Arc<AtomicBool>,
+    // Sends each preview with its position in `seeds` as soon as it is done
+    receiver: Receiver<(usize, egui::ColorImage)>,
+}
+
+impl Gallery {
+    // Generate a preview for each seed, in order, on a worker thread
+    pub fn spawn(ctx: &egui::Context, config: &TerrainConfig, seeds: Vec<u32>) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let (ctx, worker_cancel, config, worker_seeds) = (ctx.clone(), Arc::clone(&cancel), thumbnail_config(config), seeds.clone());
+        thread::spawn(move || {
+            for (slot, seed) in worker_seeds.into_iter().enumerate() {
+                if worker_cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+                if sender.send((slot, generate_terrain(&config, seed).image)).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+        Self { textures: vec![None; seeds.len()], seeds, cancel, receiver }
+    }
+
+    // Upload the previews that arrived since the last frame
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        for (slot, image) in self.receiver.try_iter() {
+            let name = format!("gallery_{}", slot);
+            self.textures[slot] = Some(ctx.load_texture(name, image, egui::TextureOptions::LINEAR));
+        }
+    }
+}
+
+impl Drop for Gallery {
+    // Stop generating previews nobody will see
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// The same world at preview size. Noise is sampled in units of the map's longer side, so a smaller map
+// still shows the same features; blocks shrink with it and supersampling is left off for speed.
+fn thumbnail_config(config: &TerrainConfig) -> TerrainConfig {
+    let factor = (THUMBNAIL_SIZE as f64 / config.width.max(config.height).max(1) as f64).min(1.0);
+    let shrink = |value: u32| ((value as f64 * factor).round() as u32).max(1);
+    TerrainConfig {
+        width: shrink(config.width),
+        height: shrink(config.height),
+        pixel_size: shrink(config.pixel_size),
+        supersample: 1,
+        ..config.clone()
+    }
+}
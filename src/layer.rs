@@ -0,0 +1,1074 @@
+// One independently configured terrain: its settings, the generated maps and their textures, the seed and
+// undo histories, and the background job that produces it
+
+use crate::erosion::DROPLET_BATCH;
+use crate::terrain::{
+    adjust_image, color_terrain, color_terrain_unadjusted, colorize_heightmap, erode_terrain, generate_terrain_with_progress, ColorMode, Curve, GeneratedTerrain, Heightmap, Levels, NoiseKind, PalettePreset,
+    Progress, TerrainConfig, TerrainMaps, WorleyDistance,
+};
+use crate::{
+    curve_editor, gradient_editor, new_seed, reset_slider, seed_from_name, thumbnail, wheel_slider, SavedConfig, CONTINENT_SCALE_RANGE, HISTOGRAM_BINS, LACUNARITY_RANGE, MAX_DIMENSION, MAX_SUPERSAMPLED_DIMENSION,
+    MAX_SEED_HISTORY, MAX_UNDO, MINIMAP_SIZE, MIN_DIMENSION, OCTAVE_RANGE, PERSISTENCE_RANGE, PROGRESS_REPAINT, REGENERATE_DEBOUNCE, SUPERSAMPLE_FACTORS, UNDO_SETTLE, WORLD_SPAN_RANGE,
+};
+use eframe::egui;
+use egui::Color32;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How far one click of an offset arrow pans, as a share of the area shown
+const OFFSET_NUDGE: f64 = 0.25;
+
+// Time units the animation advances per second of playback
+const ANIMATION_SPEED: f64 = 0.2;
+
+// A generation running on a worker thread, which sends the result along with how long the work took.
+// Jobs that show their work as they go, like erosion, also send intermediate results.
+struct GenerationJob {
+    receiver: Receiver<(GeneratedTerrain, Duration)>,
+    previews: Receiver<GeneratedTerrain>,
+    progress: Arc<Progress>,
+    // What the job does, for the timing readout: "Generated" or "Eroded"
+    kind: &'static str,
+}
+
+// What the controls changed this frame
+#[derive(Default)]
+pub struct Edits {
+    pub regenerate: bool,
+    // Changes that only affect coloring reuse the cached heights instead of resampling noise
+    pub recolor: bool,
+    pub erode: bool,
+    // Only brightness, contrast or gamma changed, which is redone on `unadjusted` rather than recolored
+    pub adjust: bool,
+    // Set when the settings came from undo or redo, which mustn't start a new undo step
+    pub restored: bool,
+}
+
+pub struct TerrainLayer {
+    pub config: TerrainConfig,
+    pub terrain: egui::ColorImage,
+    // Maps behind `terrain`
+    pub maps: TerrainMaps,
+    // Height distribution of `maps`, refreshed whenever they are replaced
+    pub histogram: Vec<usize>,
+    // Share of the map each biome covers, refreshed whenever the maps or their coloring change
+    pub biome_shares: Vec<(String, Color32, f64)>,
+    pub seed: u32,
+    // Width / height ratio captured when the size lock was turned on
+    aspect_lock: Option<f64>,
+    seed_input: String,
+    // World name the current seed was derived from, if it was typed in
+    seed_name: String,
+    // Previously used seeds, oldest first, and the entry currently shown
+    seed_history: Vec<u32>,
+    history_cursor: usize,
+    // Keeps the random actions from replacing the seed, so they only change the parameters
+    seed_locked: bool,
+    pub texture_handle: Option<egui::TextureHandle>,
+    pub minimap_handle: Option<egui::TextureHandle>,
+    // A hash per row of the image last sent to `texture_handle` and the options it was sent with, so
+    // re-uploads can skip the rows that didn't change
+    uploaded_rows: Vec<u64>,
+    texture_options: egui::TextureOptions,
+    // `terrain` before brightness, contrast and gamma, kept once one of them is adjusted and dropped
+    // whenever `terrain` is replaced
+    unadjusted: Option<egui::ColorImage>,
+    // Settings as of the last snapshot, the snapshots before and after it, and when unrecorded edits began
+    committed: (TerrainConfig, u32),
+    undo_stack: Vec<(TerrainConfig, u32)>,
+    redo_stack: Vec<(TerrainConfig, u32)>,
+    edited_since: Option<Instant>,
+    job: Option<GenerationJob>,
+    // Kind and worker time of the last job that finished
+    pub last_timing: Option<(&'static str, Duration)>,
+    // Set while showing an imported PNG that had no parameters, so there are no maps behind `terrain`
+    pub viewing_import: bool,
+    // Imported heights that generation colors in place of the noise, until Use Noise drops them
+    heightmap: Option<Arc<Heightmap>>,
+    // Pixel picked by Find Spawn, cleared whenever the maps or their coloring change
+    pub spawn: Option<[usize; 2]>,
+    // Points placed by Scatter, cleared along with the spawn
+    pub scatter: Vec<[usize; 2]>,
+    // Bumped by every new job; see `spawn_job`
+    epoch: Arc<AtomicU64>,
+    regenerate_requested: Option<Instant>,
+    // Set when colors changed while a job was running, so its result gets recolored on arrival
+    recolor_on_receive: bool,
+    // Whether the animation is playing, and the seconds played since the time last advanced
+    playing: bool,
+    unplayed: f64,
+}
+
+impl TerrainLayer {
+    // Nothing is generated until the first `regenerate_terrain`
+    pub fn new(config: TerrainConfig, seed: u32) -> Self {
+        Self {
+            committed: (config.clone(), seed),
+            config,
+            terrain: egui::ColorImage::new([256, 256], Color32::BLACK),
+            maps: TerrainMaps::default(),
+            histogram: Vec::new(),
+            biome_shares: Vec::new(),
+            seed,
+            aspect_lock: None,
+            seed_input: seed.to_string(),
+            seed_name: String::new(),
+            seed_history: vec![seed],
+            history_cursor: 0,
+            seed_locked: false,
+            texture_handle: None,
+            minimap_handle: None,
+            uploaded_rows: Vec::new(),
+            texture_options: egui::TextureOptions::NEAREST,
+            unadjusted: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            edited_since: None,
+            job: None,
+            last_timing: None,
+            viewing_import: false,
+            heightmap: None,
+            spawn: None,
+            scatter: Vec::new(),
+            epoch: Arc::new(AtomicU64::new(0)),
+            regenerate_requested: None,
+            recolor_on_receive: false,
+            playing: false,
+            unplayed: 0.0,
+        }
+    }
+
+    // Generation and coloring parameters, then the seed controls
+    pub fn show_controls(&mut self, ui: &mut egui::Ui) -> Edits {
+        let mut regenerate = false;
+        let mut recolor = false;
+        let mut erode = false;
+        let mut adjust = false;
+        let defaults = TerrainConfig::default();
+
+        if let Some([width, height]) = self.heightmap.as_ref().map(|heightmap| heightmap.size) {
+            ui.horizontal(|ui| {
+                ui.label(format!("Heights from a {}×{} import", width, height))
+                    .on_hover_text("The noise settings have no effect until you go back to the noise");
+                if ui.button("Use Noise").clicked() {
+                    self.heightmap = None;
+                    regenerate = true;
+                }
+            });
+        }
+
+        egui::ComboBox::from_label("Noise")
+            .selected_text(self.config.noise_kind.label())
+            .show_ui(ui, |ui| {
+                for kind in NoiseKind::ALL {
+                    regenerate |= ui.selectable_value(&mut self.config.noise_kind, kind, kind.label()).changed();
+                }
+            });
+        if self.config.noise_kind == NoiseKind::Worley {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Distance")
+                    .selected_text(self.config.worley.distance.label())
+                    .show_ui(ui, |ui| {
+                        for distance in WorleyDistance::ALL {
+                            regenerate |= ui.selectable_value(&mut self.config.worley.distance, distance, distance.label()).changed();
+                        }
+                    });
+                regenerate |= ui.checkbox(&mut self.config.worley.cell_values, "Cell Values").changed();
+            });
+        }
+        ui.horizontal(|ui| {
+            // Keep within what the GPU can upload as a single texture
+            let max_side = ui.ctx().input(|i| i.max_texture_side).min(MAX_DIMENSION as usize) as u32;
+            let limit = |value: f64| (value.round() as u32).clamp(MIN_DIMENSION, max_side);
+            ui.label("Size");
+            if ui.add(egui::DragValue::new(&mut self.config.width).clamp_range(MIN_DIMENSION..=max_side)).changed() {
+                if let Some(ratio) = self.aspect_lock {
+                    self.config.height = limit(self.config.width as f64 / ratio);
+                }
+                regenerate = true;
+            }
+            ui.label("×");
+            if ui.add(egui::DragValue::new(&mut self.config.height).clamp_range(MIN_DIMENSION..=max_side)).changed() {
+                if let Some(ratio) = self.aspect_lock {
+                    self.config.width = limit(self.config.height as f64 * ratio);
+                }
+                regenerate = true;
+            }
+            let mut locked = self.aspect_lock.is_some();
+            if ui.toggle_value(&mut locked, "🔗").on_hover_text("Keep the current aspect ratio while resizing").changed() {
+                self.aspect_lock = locked.then(|| self.config.width as f64 / self.config.height as f64);
+            }
+        });
+        regenerate |= ui.checkbox(&mut self.config.tileable, "Tileable").changed();
+        regenerate |= ui.checkbox(&mut self.config.ridged, "Ridged").changed();
+        ui.horizontal(|ui| {
+            ui.label("Offset").on_hover_text("World origin: pan across the noise field, in map widths at span 1");
+            regenerate |= ui.add(egui::DragValue::new(&mut self.config.offset_x).speed(0.01).prefix("x ")).changed();
+            regenerate |= ui.add(egui::DragValue::new(&mut self.config.offset_y).speed(0.01).prefix("y ")).changed();
+            for (label, [dx, dy]) in [("◀", [-1.0, 0.0]), ("▶", [1.0, 0.0]), ("▲", [0.0, -1.0]), ("▼", [0.0, 1.0])] {
+                if ui.small_button(label).clicked() {
+                    self.config.offset_x += dx * OFFSET_NUDGE * self.config.world_span;
+                    self.config.offset_y += dy * OFFSET_NUDGE * self.config.world_span;
+                    regenerate = true;
+                }
+            }
+            if ui.small_button("Center").clicked() {
+                (self.config.offset_x, self.config.offset_y) = (0.0, 0.0);
+                regenerate = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            regenerate |= ui
+                .add(egui::DragValue::new(&mut self.config.world_span).clamp_range(WORLD_SPAN_RANGE).speed(0.01).max_decimals(3))
+                .changed();
+            ui.label("World Span").on_hover_text("How many map widths of the noise field the map covers; below 1 zooms in on the origin");
+            if ui.small_button("Reset").clicked() {
+                self.config.world_span = 1.0;
+                regenerate = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            regenerate |= ui
+                .checkbox(&mut self.config.animate, "Animate")
+                .on_hover_text("Sample the noise through time so the terrain can morph; tileable maps stay still")
+                .changed();
+            ui.add_enabled_ui(self.config.animate, |ui| {
+                if ui.button(if self.playing { "⏸ Pause" } else { "▶ Play" }).clicked() {
+                    self.playing = !self.playing;
+                }
+                regenerate |= ui.add(egui::DragValue::new(&mut self.config.time).speed(0.01).max_decimals(3)).changed();
+                ui.label("Time");
+            });
+            self.playing &= self.config.animate;
+        });
+        regenerate |= reset_slider(ui, &mut self.config.continent_scale, defaults.continent_scale, CONTINENT_SCALE_RANGE, false, "Continent Scale").changed();
+        // The same setting as a frequency in cycles per pixel, for anyone used to other noise tools. The scale
+        // counts features across the map's longer side, so the feature size in pixels is 1 / frequency.
+        ui.horizontal(|ui| {
+            // World units per pixel
+            let step = self.config.world_span / self.config.width.max(self.config.height).max(1) as f64;
+            let mut frequency = self.config.continent_scale * step;
+            let bounds = CONTINENT_SCALE_RANGE.start() * step..=CONTINENT_SCALE_RANGE.end() * step;
+            let response = ui.add(egui::DragValue::new(&mut frequency).clamp_range(bounds).speed(0.0001).max_decimals(5));
+            if response.changed() {
+                self.config.continent_scale = (frequency / step).clamp(*CONTINENT_SCALE_RANGE.start(), *CONTINENT_SCALE_RANGE.end());
+                regenerate = true;
+            }
+            ui.label("Base Frequency").on_hover_text(format!("Cycles per pixel, one feature every {:.0} px", 1.0 / frequency));
+        });
+        regenerate |= reset_slider(ui, &mut self.config.detail_scale, defaults.detail_scale, 1.0..=400.0, false, "Detail Scale").changed();
+        regenerate |= reset_slider(ui, &mut self.config.detail_weight, defaults.detail_weight, 0.0..=1.0, false, "Detail Weight")
+            .on_hover_text("How much of the detail field is mixed into the continents")
+            .changed();
+        regenerate |= reset_slider(ui, &mut self.config.octaves, defaults.octaves, OCTAVE_RANGE, false, "Octaves").changed();
+        // Only a warning, so saved configs keep generating exactly as before
+        let useful = self.config.useful_octaves();
+        if self.config.octaves > useful {
+            ui.horizontal(|ui| {
+                let note = format!("Octaves past {} are finer than a pixel here and only add noise", useful);
+                ui.label(egui::RichText::new(note).small().color(Color32::DARK_RED));
+                if ui.small_button("Trim").on_hover_text("Drop the octaves that are finer than a pixel").clicked() {
+                    self.config.octaves = useful;
+                    regenerate = true;
+                }
+            });
+        }
+        egui::CollapsingHeader::new("Octave Weights").show(ui, |ui| {
+            let octaves = self.config.octaves;
+            if self.config.octave_weights.len() < octaves {
+                self.config.octave_weights.resize(octaves, 1.0);
+            }
+            for (octave, weight) in self.config.octave_weights.iter_mut().take(octaves).enumerate() {
+                regenerate |= wheel_slider(ui, weight, 0.0..=2.0, false, &format!("Octave {}", octave + 1)).changed();
+            }
+            if ui.button("Reset Weights").clicked() {
+                self.config.octave_weights.iter_mut().for_each(|weight| *weight = 1.0);
+                regenerate = true;
+            }
+        });
+        regenerate |= reset_slider(ui, &mut self.config.persistence, defaults.persistence, PERSISTENCE_RANGE, false, "Persistence").changed();
+        regenerate |= reset_slider(ui, &mut self.config.lacunarity, defaults.lacunarity, LACUNARITY_RANGE, false, "Lacunarity").changed();
+        regenerate |= reset_slider(ui, &mut self.config.pixel_size, defaults.pixel_size, 1..=16, false, "Pixel Size")
+            .on_hover_text("Size of the square blocks the terrain is sampled in")
+            .changed();
+        let block = self.config.pixel_size.max(1);
+        if !self.config.width.is_multiple_of(block) || !self.config.height.is_multiple_of(block) {
+            ui.horizontal(|ui| {
+                let note = format!("{}×{} isn't a multiple of {}, so edge blocks are cut off", self.config.width, self.config.height, block);
+                ui.label(egui::RichText::new(note).small());
+                if ui.small_button("Snap Size").on_hover_text("Round the size to the nearest whole number of blocks").clicked() {
+                    let snap = |side: u32| ((side + block / 2) / block * block).clamp(MIN_DIMENSION.div_ceil(block) * block, MAX_DIMENSION / block * block);
+                    self.config.width = snap(self.config.width);
+                    self.config.height = snap(self.config.height);
+                    regenerate = true;
+                }
+            });
+        }
+        egui::ComboBox::from_label("Supersampling")
+            .selected_text(format!("{}×", self.config.supersample.max(1)))
+            .show_ui(ui, |ui| {
+                for factor in SUPERSAMPLE_FACTORS {
+                    // The fine maps cost factor² the memory, so very large maps can't use the higher factors
+                    let fits = self.config.width.max(self.config.height) * factor <= MAX_SUPERSAMPLED_DIMENSION;
+                    let selected = self.config.supersample.max(1) == factor;
+                    let clicked = ui
+                        .add_enabled(fits, egui::SelectableLabel::new(selected, format!("{}×", factor)))
+                        .on_disabled_hover_text("Too large at this map size")
+                        .clicked();
+                    if clicked && !selected {
+                        self.config.supersample = factor;
+                        regenerate = true;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Generate and color at a higher resolution, then average down for smooth color edges. Blocky maps (Pixel Size above 1) look the same.");
+        regenerate |= reset_slider(ui, &mut self.config.warp_strength, defaults.warp_strength, 0.0..=4.0, false, "Warp Strength").changed();
+        regenerate |= reset_slider(ui, &mut self.config.falloff, defaults.falloff, 0.0..=1.0, false, "Island Falloff").changed();
+        ui.horizontal(|ui| {
+            regenerate |= ui.checkbox(&mut self.config.auto_normalize, "Auto Normalize").changed();
+            regenerate |= ui
+                .checkbox(&mut self.config.equalize, "Equalize")
+                .on_hover_text("Spread the heights so every elevation band covers about the same area")
+                .changed();
+            regenerate |= ui.checkbox(&mut self.config.invert, "Invert").on_hover_text("Turn peaks into trenches and oceans into highlands").changed();
+        });
+        egui::CollapsingHeader::new("Levels").show(ui, |ui| {
+            let levels = &mut self.config.levels;
+            egui::Grid::new("levels").show(ui, |ui| {
+                ui.label("Input").on_hover_text("Heights at or below black and at or above white are clipped");
+                regenerate |= ui.add(egui::DragValue::new(&mut levels.input_black).clamp_range(0.0..=1.0).speed(0.005).prefix("black ")).changed();
+                regenerate |= ui.add(egui::DragValue::new(&mut levels.input_white).clamp_range(0.0..=1.0).speed(0.005).prefix("white ")).changed();
+                ui.end_row();
+                ui.label("Output").on_hover_text("The range the clipped heights are stretched onto");
+                regenerate |= ui.add(egui::DragValue::new(&mut levels.output_black).clamp_range(0.0..=1.0).speed(0.005).prefix("black ")).changed();
+                regenerate |= ui.add(egui::DragValue::new(&mut levels.output_white).clamp_range(0.0..=1.0).speed(0.005).prefix("white ")).changed();
+                ui.end_row();
+            });
+            if ui.button("Reset Levels").clicked() {
+                *levels = Levels::default();
+                regenerate = true;
+            }
+        });
+        egui::CollapsingHeader::new("Elevation Curve").show(ui, |ui| {
+            ui.label("Drag points to remap heights. Double-click to add a point, right-click to remove one.");
+            regenerate |= curve_editor(ui, &mut self.config.elevation_curve);
+            if ui.button("Reset Curve").clicked() {
+                self.config.elevation_curve = Curve::default();
+                regenerate = true;
+            }
+        });
+        egui::CollapsingHeader::new("Erosion").show(ui, |ui| {
+            regenerate |= ui.checkbox(&mut self.config.thermal_erosion, "Thermal Erosion").changed();
+            ui.add_enabled_ui(self.config.thermal_erosion, |ui| {
+                regenerate |= reset_slider(ui, &mut self.config.thermal_iterations, defaults.thermal_iterations, 1..=500, false, "Iterations").changed();
+                regenerate |= reset_slider(ui, &mut self.config.talus_angle, defaults.talus_angle, 1.0..=89.0, false, "Talus Angle")
+                    .on_hover_text("Slopes steeper than this many degrees shed material downhill")
+                    .changed();
+            });
+            ui.separator();
+            reset_slider(ui, &mut self.config.droplets, defaults.droplets, 1_000..=500_000, true, "Droplets");
+            reset_slider(ui, &mut self.config.erosion_rate, defaults.erosion_rate, 0.0..=1.0, false, "Erosion Rate");
+            reset_slider(ui, &mut self.config.deposition_rate, defaults.deposition_rate, 0.0..=1.0, false, "Deposition Rate");
+            reset_slider(ui, &mut self.config.evaporation, defaults.evaporation, 0.0..=0.1, false, "Evaporation");
+            // Erodes whatever is currently shown, so wait for any running job first
+            let ready = self.job.is_none() && self.maps.size == self.terrain.size;
+            if ui.add_enabled(ready, egui::Button::new("Simulate Erosion")).clicked() {
+                erode = true;
+            }
+            if let Some(job) = self.job.as_ref().filter(|job| job.kind == "Eroded") {
+                ui.horizontal(|ui| {
+                    let (step, steps) = job.progress.steps();
+                    ui.label(format!("Step {} / {}", step, steps))
+                        .on_hover_text(format!("Each step simulates {} droplets", DROPLET_BATCH));
+                    let paused = job.progress.is_paused();
+                    if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+                        job.progress.set_paused(!paused);
+                    }
+                });
+            }
+        });
+        recolor |= reset_slider(ui, &mut self.config.sea_level, defaults.sea_level, 0.0..=1.0, false, "Sea Level").changed();
+        recolor |= reset_slider(ui, &mut self.config.latitude_influence, defaults.latitude_influence, 0.0..=1.0, false, "Latitude Influence")
+            .on_hover_text("Colder land biomes towards the top and bottom edges, warmer in the middle")
+            .changed();
+        ui.horizontal(|ui| {
+            recolor |= ui.checkbox(&mut self.config.snow_line, "Snow Line").on_hover_text("Snow above a line that drops towards the top and bottom edges").changed();
+            ui.add_enabled_ui(self.config.snow_line, |ui| {
+                recolor |= reset_slider(ui, &mut self.config.snow_level, defaults.snow_level, 0.0..=1.0, false, "Level").changed();
+                recolor |= reset_slider(ui, &mut self.config.snow_latitude, defaults.snow_latitude, 0.0..=1.0, false, "Polar Drop").changed();
+            });
+        });
+        regenerate |= reset_slider(ui, &mut self.config.terrace_steps, defaults.terrace_steps, 0..=32, false, "Terrace Steps")
+            .on_hover_text("Flatten the heights into stepped plateaus; 0 turns terracing off")
+            .changed();
+        ui.horizontal(|ui| {
+            regenerate |= reset_slider(ui, &mut self.config.plateau_sharpness, defaults.plateau_sharpness, 0.0..=1.0, false, "Plateau")
+                .on_hover_text("Flatten everything above the level into mesas; 0 turns it off")
+                .changed();
+            let enabled = self.config.plateau_sharpness > 0.0;
+            regenerate |= ui.add_enabled_ui(enabled, |ui| reset_slider(ui, &mut self.config.plateau_level, defaults.plateau_level, 0.0..=1.0, false, "Level")).inner.changed();
+        });
+
+        egui::CollapsingHeader::new("Biome Bands").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.menu_button("Presets", |ui| {
+                    for preset in PalettePreset::ALL {
+                        if ui.button(preset.label()).clicked() {
+                            self.config.apply_palette(preset);
+                            recolor = true;
+                            ui.close_menu();
+                        }
+                    }
+                });
+                recolor |= ui.checkbox(&mut self.config.posterize, "Posterize").changed();
+            });
+            // Each threshold can only move between its neighbours', so the bands stay sorted while dragging
+            let thresholds: Vec<f64> = self.config.bands.iter().map(|band| band.threshold).collect();
+            let (mut insert, mut remove) = (None, None);
+            let removable = self.config.bands.len() > 1;
+            for (index, band) in self.config.bands.iter_mut().enumerate() {
+                let floor = index.checked_sub(1).map_or(0.0, |previous| thresholds[previous]);
+                let ceiling = thresholds.get(index + 1).copied().unwrap_or(1.0);
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut band.name).desired_width(80.0));
+                    recolor |= wheel_slider(ui, &mut band.threshold, floor..=ceiling, false, "").changed();
+                    recolor |= ui.color_edit_button_srgba(&mut band.color).changed();
+                    recolor |= ui.checkbox(&mut band.water, "Water").changed();
+                    if ui.small_button("+").on_hover_text("Insert a band below this one").clicked() {
+                        insert = Some(index);
+                    }
+                    // Biome lookup needs at least one band
+                    if ui.add_enabled(removable, egui::Button::new("–").small()).on_hover_text("Remove this band").clicked() {
+                        remove = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = insert {
+                self.config.insert_band(index);
+                recolor = true;
+            }
+            if let Some(index) = remove {
+                self.config.bands.remove(index);
+                recolor = true;
+            }
+            if self.config.bands.is_empty() && ui.button("Add Band").clicked() {
+                self.config.insert_band(0);
+                recolor = true;
+            }
+            if recolor {
+                self.config.sort_bands();
+            }
+        });
+
+        egui::ComboBox::from_label("Coloring")
+            .selected_text(self.config.color_mode.label())
+            .show_ui(ui, |ui| {
+                for mode in ColorMode::ALL {
+                    // The moisture field is only generated for the biome matrix
+                    regenerate |= ui.selectable_value(&mut self.config.color_mode, mode, mode.label()).changed();
+                }
+            });
+        recolor |= reset_slider(ui, &mut self.config.texture_amount, defaults.texture_amount, 0.0..=1.0, false, "Texture")
+            .on_hover_text("Vary the brightness from pixel to pixel to break up flat fills; 0 keeps solid colors")
+            .changed();
+        if self.config.color_mode == ColorMode::Biomes {
+            egui::CollapsingHeader::new("Biome Matrix").show(ui, |ui| {
+                ui.label("Rows: elevation above sea level (top = highest). Columns: dry to wet.");
+                egui::Grid::new("biome_matrix").show(ui, |ui| {
+                    for row in self.config.biome_matrix.iter_mut().rev() {
+                        for cell in row.iter_mut() {
+                            ui.vertical(|ui| {
+                                recolor |= ui.color_edit_button_srgba(&mut cell.color).changed();
+                                ui.add(egui::TextEdit::singleline(&mut cell.name).desired_width(70.0));
+                            });
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        }
+        if self.config.color_mode == ColorMode::Gradient {
+            egui::CollapsingHeader::new("Gradient").show(ui, |ui| {
+                recolor |= gradient_editor(ui, &mut self.config.gradient);
+            });
+        }
+
+        egui::CollapsingHeader::new("Shading").show(ui, |ui| {
+            recolor |= ui.checkbox(&mut self.config.hillshade, "Hillshade").changed();
+            ui.add_enabled_ui(self.config.hillshade, |ui| {
+                recolor |= reset_slider(ui, &mut self.config.shading_strength, defaults.shading_strength, 0.0..=1.0, false, "Strength").changed();
+                recolor |= reset_slider(ui, &mut self.config.light_azimuth, defaults.light_azimuth, 0.0..=360.0, false, "Light Azimuth").changed();
+                recolor |= reset_slider(ui, &mut self.config.light_altitude, defaults.light_altitude, 0.0..=90.0, false, "Light Altitude").changed();
+            });
+            reset_slider(ui, &mut self.config.normal_strength, defaults.normal_strength, 0.1..=10.0, true, "Normal Map Strength")
+                .on_hover_text("Slope exaggeration for Save Normal Map");
+        });
+
+        egui::CollapsingHeader::new("Adjustments").show(ui, |ui| {
+            adjust |= reset_slider(ui, &mut self.config.brightness, defaults.brightness, -0.5..=0.5, false, "Brightness").changed();
+            adjust |= reset_slider(ui, &mut self.config.contrast, defaults.contrast, 0.0..=3.0, false, "Contrast").changed();
+            adjust |= reset_slider(ui, &mut self.config.gamma, defaults.gamma, 0.2..=5.0, true, "Gamma").changed();
+            if ui.button("Reset Adjustments").clicked() {
+                (self.config.brightness, self.config.contrast, self.config.gamma) = (0.0, 1.0, 1.0);
+                adjust = true;
+            }
+        });
+
+        egui::CollapsingHeader::new("Water").show(ui, |ui| {
+            recolor |= ui.checkbox(&mut self.config.depth_shading, "Depth Shading").on_hover_text("Darken water the further it is below the sea level").changed();
+            ui.add_enabled_ui(self.config.depth_shading, |ui| {
+                ui.horizontal(|ui| {
+                    recolor |= ui.color_edit_button_srgba(&mut self.config.shallow_color).changed();
+                    ui.label("Shallow");
+                    recolor |= ui.color_edit_button_srgba(&mut self.config.deep_color).changed();
+                    ui.label("Deep");
+                });
+            });
+        });
+
+        egui::CollapsingHeader::new("Contours").show(ui, |ui| {
+            recolor |= ui.checkbox(&mut self.config.contours, "Contour Lines").changed();
+            ui.add_enabled_ui(self.config.contours, |ui| {
+                recolor |= reset_slider(ui, &mut self.config.contour_interval, defaults.contour_interval, 0.01..=0.25, false, "Interval").changed();
+                ui.horizontal(|ui| {
+                    recolor |= ui.color_edit_button_srgba(&mut self.config.contour_color).changed();
+                    ui.label("Line Color");
+                });
+            });
+        });
+
+        egui::CollapsingHeader::new("Coastline").show(ui, |ui| {
+            recolor |= reset_slider(ui, &mut self.config.coastline_sharpness, defaults.coastline_sharpness, 0.0..=1.0, false, "Sharpness")
+                .on_hover_text("Narrow the shallows and beaches around the sea level; 1 is a hard cut from water to land")
+                .changed();
+            recolor |= ui.checkbox(&mut self.config.coastline, "Outline").on_hover_text("Stroke the land along the sea level").changed();
+            ui.add_enabled_ui(self.config.coastline, |ui| {
+                recolor |= reset_slider(ui, &mut self.config.coastline_width, defaults.coastline_width, 1..=8, false, "Width").changed();
+                ui.horizontal(|ui| {
+                    recolor |= ui.color_edit_button_srgba(&mut self.config.coastline_color).changed();
+                    ui.label("Stroke Color");
+                });
+            });
+        });
+
+        egui::CollapsingHeader::new("Rivers").show(ui, |ui| {
+            if ui.checkbox(&mut self.config.rivers, "Rivers").changed() {
+                // Drainage is only computed while rivers are on. Without supersampling it comes straight from the
+                // current heights, which keeps any erosion; a supersampled map needs its fine heights regenerated.
+                if self.config.rivers && self.maps.flow.is_empty() {
+                    if self.maps.fine.is_none() && !self.maps.heights.is_empty() {
+                        self.maps.update_flow(&self.config);
+                        recolor = true;
+                    } else {
+                        regenerate = true;
+                    }
+                } else {
+                    recolor = true;
+                }
+            }
+            ui.add_enabled_ui(self.config.rivers, |ui| {
+                recolor |= reset_slider(ui, &mut self.config.river_threshold, defaults.river_threshold, 0.0001..=0.05, true, "Threshold")
+                    .on_hover_text("Share of the map that must drain through a cell for it to become a river")
+                    .changed();
+                ui.horizontal(|ui| {
+                    recolor |= ui.color_edit_button_srgba(&mut self.config.river_color).changed();
+                    ui.label("River Color");
+                });
+            });
+        });
+
+        egui::CollapsingHeader::new("Regions").show(ui, |ui| {
+            recolor |= ui
+                .checkbox(&mut self.config.regions, "Regions")
+                .on_hover_text("Split the land into Voronoi regions placed from the seed, such as nations")
+                .changed();
+            ui.add_enabled_ui(self.config.regions, |ui| {
+                recolor |= reset_slider(ui, &mut self.config.region_count, defaults.region_count, 1..=200, false, "Count").changed();
+                recolor |= reset_slider(ui, &mut self.config.region_opacity, defaults.region_opacity, 0.0..=1.0, false, "Opacity")
+                    .on_hover_text("How much of the region color covers the biome colors")
+                    .changed();
+                ui.horizontal(|ui| {
+                    recolor |= ui.checkbox(&mut self.config.region_borders, "Borders").changed();
+                    recolor |= ui.color_edit_button_srgba(&mut self.config.region_border_color).changed();
+                });
+            });
+        });
+
+        ui.horizontal(|ui| {
+            let new_seed = ui
+                .add_enabled(!self.seed_locked, egui::Button::new("New Seed"))
+                .on_hover_text("Shortcut: Space")
+                .on_disabled_hover_text("The seed is locked");
+            if new_seed.clicked() {
+                regenerate |= self.reroll_seed();
+            }
+            let randomized = if self.seed_locked { "Scale, octaves, persistence and lacunarity; the seed is locked" } else { "New seed, scale, octaves, persistence and lacunarity" };
+            if ui.button("Randomize").on_hover_text(randomized).clicked() {
+                self.randomize_parameters();
+                regenerate = true;
+            }
+            if ui.button("Reset to Defaults").on_hover_text("Default settings with a new seed; undo brings the old ones back").clicked() {
+                self.reset_to_defaults();
+                regenerate = true;
+            }
+            ui.label(format!("Seed: {}", self.seed));
+            ui.toggle_value(&mut self.seed_locked, "🔒").on_hover_text("Keep this seed when using New Seed, Randomize or Reset to Defaults");
+            let (at_start, at_end) = (self.history_cursor == 0, self.history_cursor + 1 >= self.seed_history.len());
+            if ui.add_enabled(!at_start, egui::Button::new("◀ Prev")).clicked() {
+                regenerate |= self.step_history(false);
+            }
+            if ui.add_enabled(!at_end, egui::Button::new("Next ▶")).clicked() {
+                regenerate |= self.step_history(true);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Enter seed");
+            let response = ui.add(egui::TextEdit::singleline(&mut self.seed_input).desired_width(100.0));
+            let parsed = self.seed_input.trim().parse::<u32>();
+            if response.changed() {
+                if let Ok(seed) = parsed {
+                    if seed != self.seed {
+                        self.set_seed(seed);
+                        regenerate = true;
+                    }
+                }
+            }
+            if parsed.is_err() {
+                ui.label(egui::RichText::new("Not a valid u32").small().color(Color32::RED));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("World name");
+            let response = ui.add(egui::TextEdit::singleline(&mut self.seed_name).desired_width(140.0));
+            let name = self.seed_name.trim();
+            if !name.is_empty() {
+                let seed = seed_from_name(name);
+                if response.changed() && seed != self.seed {
+                    self.set_seed(seed);
+                    self.seed_input = seed.to_string();
+                    regenerate = true;
+                }
+                ui.label(format!("→ seed {}", seed));
+            }
+        });
+        Edits { regenerate, recolor, erode, adjust, restored: false }
+    }
+
+    // Fraction done of the running job, if there is one
+    pub fn progress(&self) -> Option<f32> {
+        self.job.as_ref().map(|job| job.progress.fraction())
+    }
+
+    // Whether there is anything to show, or on its way
+    pub fn is_started(&self) -> bool {
+        self.texture_handle.is_some() || self.job.is_some()
+    }
+
+    // Act on this frame's edits once the controls have been drawn: record undo history, pick up a finished
+    // job, recolor, and start erosion or (debounced) regeneration
+    pub fn finish_frame(&mut self, ctx: &egui::Context, mut edits: Edits) {
+        if (edits.regenerate || edits.recolor || edits.adjust) && !edits.restored {
+            self.edited_since = Some(Instant::now());
+        }
+        self.record_history(ctx);
+
+        if let Some(job) = &self.job {
+            match job.receiver.try_recv() {
+                Ok((terrain, elapsed)) => {
+                    self.last_timing = Some((job.kind, elapsed));
+                    self.terrain = terrain.image;
+                    self.unadjusted = None;
+                    self.maps = terrain.maps;
+                    self.spawn = None;
+                    self.scatter.clear();
+                    self.viewing_import = false;
+                    self.histogram = self.maps.histogram(HISTOGRAM_BINS);
+                    self.biome_shares = self.config.biome_shares(&self.maps);
+                    self.job = None;
+                    // The job was colored with the config it started with
+                    edits.recolor |= std::mem::take(&mut self.recolor_on_receive);
+                    self.update_texture(ctx);
+                }
+                // Show the latest intermediate result, if any, and keep repainting so the progress bar moves
+                Err(TryRecvError::Empty) => {
+                    if let Some(preview) = job.previews.try_iter().last() {
+                        self.terrain = preview.image;
+                        self.unadjusted = None;
+                        self.maps = preview.maps;
+                        self.update_texture(ctx);
+                    }
+                    ctx.request_repaint_after(PROGRESS_REPAINT);
+                }
+                Err(TryRecvError::Disconnected) => self.job = None,
+            }
+        }
+
+        // Each animation frame starts once the last one has arrived and advances by all the time played since,
+        // so the terrain morphs at the same speed however long a frame takes to generate
+        if self.playing {
+            self.unplayed += ctx.input(|i| i.stable_dt) as f64;
+            if self.job.is_none() {
+                self.config.time += std::mem::take(&mut self.unplayed) * ANIMATION_SPEED;
+                self.regenerate_requested = None;
+                self.regenerate_terrain(ctx);
+            }
+            ctx.request_repaint();
+        }
+
+        if edits.recolor {
+            self.recolor_terrain(ctx);
+        } else if edits.adjust {
+            self.adjust_terrain(ctx);
+        }
+        if edits.erode {
+            self.simulate_erosion(ctx);
+        }
+
+        // Wait for the controls to settle before starting a job, so dragging a slider doesn't spawn one per frame
+        if edits.regenerate {
+            self.regenerate_requested = Some(Instant::now());
+        }
+        if let Some(requested) = self.regenerate_requested {
+            let elapsed = requested.elapsed();
+            if elapsed >= REGENERATE_DEBOUNCE {
+                self.regenerate_requested = None;
+                self.regenerate_terrain(ctx);
+            } else {
+                ctx.request_repaint_after(REGENERATE_DEBOUNCE - elapsed);
+            }
+        }
+    }
+
+    // Hand the current parameters to a worker thread; the result is picked up in `finish_frame`
+    pub fn regenerate_terrain(&mut self, ctx: &egui::Context) {
+        let config = self.config.clone();
+        let seed = self.seed;
+        let heightmap = self.heightmap.clone();
+        self.spawn_job(ctx, "Generated", move |progress, _| match &heightmap {
+            Some(heightmap) => colorize_heightmap(&config, heightmap, seed, progress),
+            None => generate_terrain_with_progress(&config, seed, progress),
+        });
+    }
+
+    // Generate from imported heights rather than the noise, at the heightmap's own size as far as the
+    // size limits allow; anything outside them is resized to fit
+    pub fn use_heightmap(&mut self, heightmap: Heightmap) {
+        let limit = |side: usize| (side as u32).clamp(MIN_DIMENSION, MAX_DIMENSION);
+        self.config.width = limit(heightmap.size[0]);
+        self.config.height = limit(heightmap.size[1]);
+        self.aspect_lock = None;
+        self.heightmap = Some(Arc::new(heightmap));
+    }
+
+    // Erode a copy of the current maps in the background, showing the terrain as it erodes; the result
+    // replaces the maps like a generation job
+    fn simulate_erosion(&mut self, ctx: &egui::Context) {
+        let config = self.config.clone();
+        let maps = self.maps.clone();
+        let seed = self.seed;
+        self.spawn_job(ctx, "Eroded", move |progress, preview| erode_terrain(&config, maps, seed, progress, preview));
+    }
+
+    // Starting a job bumps the epoch, so any older job still running abandons its work instead of finishing.
+    // `work` may pass intermediate results to its second argument to have them shown before it is done.
+    fn spawn_job(
+        &mut self,
+        ctx: &egui::Context,
+        kind: &'static str,
+        work: impl FnOnce(&Progress, &mut dyn FnMut(GeneratedTerrain)) -> Option<GeneratedTerrain> + Send + 'static,
+    ) {
+        let ctx = ctx.clone();
+        let progress = Arc::new(Progress::for_epoch(&self.epoch));
+        let worker_progress = Arc::clone(&progress);
+        let (sender, receiver) = mpsc::channel();
+        let (preview_sender, previews) = mpsc::channel();
+        thread::spawn(move || {
+            let started = Instant::now();
+            let mut preview = |terrain| {
+                if preview_sender.send(terrain).is_ok() {
+                    ctx.request_repaint();
+                }
+            };
+            // A stale job sends nothing, and the receiver is gone anyway once a newer job replaced this one
+            if let Some(terrain) = work(&worker_progress, &mut preview) {
+                if sender.send((terrain, started.elapsed())).is_ok() {
+                    ctx.request_repaint();
+                }
+            }
+        });
+        // Replacing the previous job drops its receivers, so even a result that slipped through is discarded
+        self.job = Some(GenerationJob { receiver, previews, progress, kind });
+    }
+
+    // Once the edits have settled, push the previous snapshot and start a new one from the current settings
+    fn record_history(&mut self, ctx: &egui::Context) {
+        let Some(edited) = self.edited_since else {
+            return;
+        };
+        let elapsed = edited.elapsed();
+        if elapsed < UNDO_SETTLE || ctx.memory(|m| m.is_anything_being_dragged()) {
+            ctx.request_repaint_after(UNDO_SETTLE.saturating_sub(elapsed));
+            return;
+        }
+        self.commit_snapshot();
+    }
+
+    fn commit_snapshot(&mut self) {
+        if self.edited_since.take().is_none() {
+            return;
+        }
+        let current = (self.config.clone(), self.seed);
+        self.undo_stack.push(std::mem::replace(&mut self.committed, current));
+        if self.undo_stack.len() > MAX_UNDO {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    // Both return whether the settings changed and need regenerating
+    pub fn undo(&mut self) -> bool {
+        // Edits that haven't settled yet are undone as one step
+        self.commit_snapshot();
+        let Some(previous) = self.undo_stack.pop() else {
+            return false;
+        };
+        let current = std::mem::replace(&mut self.committed, previous);
+        self.redo_stack.push(current);
+        self.restore_committed();
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if self.edited_since.is_some() {
+            return false;
+        }
+        let Some(next) = self.redo_stack.pop() else {
+            return false;
+        };
+        let current = std::mem::replace(&mut self.committed, next);
+        self.undo_stack.push(current);
+        self.restore_committed();
+        true
+    }
+
+    fn restore_committed(&mut self) {
+        let (config, seed) = self.committed.clone();
+        self.config = config;
+        self.seed = seed;
+        self.seed_input = seed.to_string();
+    }
+
+    // Does nothing while the seed is locked; returns whether the seed changed
+    pub fn reroll_seed(&mut self) -> bool {
+        if self.seed_locked {
+            return false;
+        }
+        self.use_seed(new_seed());
+        true
+    }
+
+    // Switch to a seed chosen outside the seed field, such as from the gallery, and show it there
+    pub fn use_seed(&mut self, seed: u32) {
+        self.set_seed(seed);
+        self.seed_input = seed.to_string();
+    }
+
+    // Reroll the seed (unless it is locked) along with the main noise parameters, each anywhere in its
+    // slider's range. The parameters are drawn from a fresh seed too, so they vary even while the seed is
+    // locked.
+    pub fn randomize_parameters(&mut self) {
+        let mut rng = StdRng::seed_from_u64(new_seed().into());
+        self.config.continent_scale = rng.gen_range(CONTINENT_SCALE_RANGE);
+        self.config.octaves = rng.gen_range(OCTAVE_RANGE);
+        self.config.persistence = rng.gen_range(PERSISTENCE_RANGE);
+        self.config.lacunarity = rng.gen_range(LACUNARITY_RANGE);
+        self.reroll_seed();
+    }
+
+    // Start over from the default settings and a fresh seed, or the same one while it is locked
+    pub fn reset_to_defaults(&mut self) {
+        self.config = TerrainConfig::default();
+        self.aspect_lock = None;
+        self.reroll_seed();
+    }
+
+    // Switch to a new seed and record it in the history, dropping anything after the current entry
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.seed_history.truncate(self.history_cursor + 1);
+        self.seed_history.push(seed);
+        if self.seed_history.len() > MAX_SEED_HISTORY {
+            self.seed_history.remove(0);
+        }
+        self.history_cursor = self.seed_history.len() - 1;
+    }
+
+    // Move through the seed history without recording anything; returns whether the seed changed
+    pub fn step_history(&mut self, forward: bool) -> bool {
+        let cursor = if forward {
+            self.history_cursor + 1
+        } else {
+            match self.history_cursor.checked_sub(1) {
+                Some(cursor) => cursor,
+                None => return false,
+            }
+        };
+        let Some(&seed) = self.seed_history.get(cursor) else {
+            return false;
+        };
+        self.history_cursor = cursor;
+        self.seed = seed;
+        self.seed_input = seed.to_string();
+        true
+    }
+
+    // Rebuild the image from the cached heights with the current coloring options
+    pub fn recolor_terrain(&mut self, ctx: &egui::Context) {
+        if self.job.is_some() {
+            self.recolor_on_receive = true;
+        }
+        if self.maps.size == self.terrain.size {
+            self.terrain = color_terrain(&self.config, &self.maps);
+            self.unadjusted = None;
+            self.spawn = None;
+            self.scatter.clear();
+            self.biome_shares = self.config.biome_shares(&self.maps);
+            self.update_texture(ctx);
+        }
+    }
+
+    // Redo only the brightness, contrast and gamma pass, starting from the image as it was before it
+    fn adjust_terrain(&mut self, ctx: &egui::Context) {
+        if self.job.is_some() {
+            self.recolor_on_receive = true;
+        }
+        if self.unadjusted.is_none() && self.maps.size == self.terrain.size {
+            self.unadjusted = Some(color_terrain_unadjusted(&self.config, &self.maps));
+        }
+        if let Some(unadjusted) = &self.unadjusted {
+            let mut image = unadjusted.clone();
+            adjust_image(&self.config, &mut image);
+            self.terrain = image;
+            self.update_texture(ctx);
+        }
+    }
+
+    // The image the view mode shows, which is also what the PNG, tile and clipboard exports write
+    pub fn view_image(&self) -> Cow<'_, egui::ColorImage> {
+        self.config.view_mode.image(&self.maps, &self.terrain)
+    }
+
+    // Send the view image to the GPU. An existing texture of the same size and filter is updated in
+    // place, and only across the span of rows that differ from the last upload, so small edits to a large
+    // map don't resend all of it; an unchanged image isn't sent at all.
+    pub fn update_texture(&mut self, ctx: &egui::Context) {
+        let options = egui::TextureOptions {
+            magnification: self.config.texture_filter,
+            minification: self.config.texture_filter,
+        };
+        // Not `view_image`, which would borrow all of `self` while the texture handle is updated
+        let view = self.config.view_mode.image(&self.maps, &self.terrain);
+        let image = view.as_ref();
+        let rows = row_hashes(image);
+        match &mut self.texture_handle {
+            Some(handle) if handle.size() == image.size && self.texture_options == options => {
+                let differs = |(new, old): (&u64, &u64)| new != old;
+                let first = rows.iter().zip(&self.uploaded_rows).position(differs);
+                let last = rows.iter().zip(&self.uploaded_rows).rposition(differs);
+                let (Some(first), Some(last)) = (first, last) else {
+                    return;
+                };
+                let width = image.size[0];
+                let pixels = image.pixels[first * width..(last + 1) * width].to_vec();
+                handle.set_partial([0, first], egui::ColorImage { size: [width, last + 1 - first], pixels }, options);
+            }
+            Some(handle) => handle.set(image.clone(), options),
+            None => self.texture_handle = Some(ctx.load_texture("terrain", image.clone(), options)),
+        }
+        self.uploaded_rows = rows;
+        self.texture_options = options;
+
+        let minimap = thumbnail(image, MINIMAP_SIZE);
+        match &mut self.minimap_handle {
+            Some(handle) => handle.set(minimap, egui::TextureOptions::LINEAR),
+            None => self.minimap_handle = Some(ctx.load_texture("minimap", minimap, egui::TextureOptions::LINEAR)),
+        }
+    }
+
+    pub fn saved_config(&self) -> SavedConfig {
+        SavedConfig {
+            seed: Some(self.seed),
+            config: self.config.clone(),
+        }
+    }
+
+    // Returns a note for each setting that was outside the limits and had to change
+    pub fn apply_saved(&mut self, mut saved: SavedConfig) -> Vec<String> {
+        let notes = saved.validate();
+        // Saved parameters describe noise, so they replace any imported heights
+        self.heightmap = None;
+        self.config = saved.config;
+        if let Some(seed) = saved.seed {
+            self.set_seed(seed);
+            self.seed_input = seed.to_string();
+        }
+        notes
+    }
+
+    // Whether the heights come from an imported heightmap, which the config and seed can't reproduce
+    pub fn uses_heightmap(&self) -> bool {
+        self.heightmap.is_some()
+    }
+
+    // Nothing derived from the maps can be written until the first generation job has finished
+    pub fn check_maps(&self) -> image::ImageResult<()> {
+        if self.maps.size != self.terrain.size {
+            return Err(image::ImageError::Parameter(image::error::ParameterError::from_kind(
+                image::error::ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+        Ok(())
+    }
+
+    // Show an image that has no maps behind it, such as an imported PNG without parameters, until the next
+    // generation replaces it
+    pub fn show_image(&mut self, ctx: &egui::Context, image: egui::ColorImage) {
+        // Bump the epoch too, so a job still running stops instead of finishing unseen
+        self.epoch.fetch_add(1, Ordering::Relaxed);
+        self.job = None;
+        self.regenerate_requested = None;
+        self.unadjusted = Some(image.clone());
+        self.terrain = image;
+        self.maps = TerrainMaps::default();
+        self.histogram = self.maps.histogram(HISTOGRAM_BINS);
+        self.biome_shares.clear();
+        self.spawn = None;
+        self.scatter.clear();
+        self.viewing_import = true;
+        self.update_texture(ctx);
+    }
+}
+
+// Hash every row of an image, in parallel since it runs on the UI thread for maps up to 4096 pixels a side
+fn row_hashes(image: &egui::ColorImage) -> Vec<u64> {
+    image
+        .pixels
+        .par_chunks(image.size[0].max(1))
+        .map(|row| {
+            let mut hasher = DefaultHasher::new();
+            row.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
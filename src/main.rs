@@ -1,32 +1,242 @@
-use noise::{NoiseFn, Perlin};
+mod batch;
+mod cli;
+mod erosion;
+mod export;
+mod gallery;
+mod layer;
+mod preview3d;
+mod regions;
+mod rivers;
+mod scatter;
+mod spawn;
+mod terrain;
+
 use rand::Rng;
 use eframe::{egui};
 use egui::Color32;
 use egui::{FontDefinitions, FontFamily};
-use std::fs::File;
-use std::io::Read;
+use serde::{Deserialize, Serialize};
+use batch::BatchJob;
+use gallery::Gallery;
+use preview3d::Orbit;
+use export::{load_heightmap, load_png, save_heightmap, save_normal_map, save_obj, save_png, save_points, save_tiles};
+use layer::{Edits, TerrainLayer};
+use terrain::{Curve, Gradient, Interpolation, TerrainConfig, TerrainMaps, ViewMode};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Number of seeds kept for Prev/Next
+const MAX_SEED_HISTORY: usize = 100;
+
+// Range for the generated width and height
+const MIN_DIMENSION: u32 = 64;
+const MAX_DIMENSION: u32 = 4096;
+
+// Supersampling choices, and the largest side the full-resolution maps may reach
+const SUPERSAMPLE_FACTORS: [u32; 3] = [1, 2, 4];
+const MAX_SUPERSAMPLED_DIMENSION: u32 = 8192;
+
+// Seeds shown at once in the Seed Gallery, and how many previews fit in a row
+const GALLERY_SEEDS: u32 = 9;
+const GALLERY_COLUMNS: usize = 3;
+
+// Smallest tile Export Tiles will cut
+const MIN_TILE_SIZE: u32 = 16;
+
+// Slider ranges for the main noise parameters, also the bounds for Randomize
+const CONTINENT_SCALE_RANGE: RangeInclusive<f64> = 1.0..=100.0;
+const OCTAVE_RANGE: RangeInclusive<usize> = 1..=8;
+const PERSISTENCE_RANGE: RangeInclusive<f64> = 0.0..=1.0;
+const LACUNARITY_RANGE: RangeInclusive<f64> = 1.0..=4.0;
+
+// World Span limits, in map widths
+const WORLD_SPAN_RANGE: RangeInclusive<f64> = 0.01..=100.0;
+
+// Starting width of the parameter panel, per terrain shown
+const CONTROLS_WIDTH: f32 = 340.0;
+
+// Preview zoom limits and how strongly one scroll step zooms
+const MAX_ZOOM: f32 = 32.0;
+const ZOOM_SPEED: f32 = 0.002;
+
+// Smallest scale the preview is fitted at, so a cramped window still shows something
+const MIN_PREVIEW_SCALE: f32 = 0.05;
+
+// Smallest on-screen grid cell, in points, that still gets coordinate labels
+const GRID_LABEL_SPACING: f32 = 48.0;
+
+// On-screen spacing of the flow arrows in points, and the slope (height per map width) drawn at full length
+const FLOW_ARROW_SPACING: f32 = 28.0;
+const FLOW_FULL_SLOPE: f64 = 4.0;
+
+// Longer side of the overview thumbnail, in pixels
+const MINIMAP_SIZE: usize = 128;
+
+// Elevation histogram resolution and on-screen height
+const HISTOGRAM_BINS: usize = 64;
+const HISTOGRAM_HEIGHT: f32 = 80.0;
+
+const LEGEND_SWATCH_SIZE: f32 = 12.0;
+
+// Stem length and head radius of the spawn pin, in points
+const PIN_HEIGHT: f32 = 16.0;
+const PIN_RADIUS: f32 = 5.0;
+
+// Radius of a scattered point's marker, in points
+const MARKER_RADIUS: f32 = 3.0;
 
-// Define terrain parameters
-struct TerrainConfig {
-    width: u32,
-    height: u32,
-    scale: f64,
-    octaves: usize,
-    persistence: f64,
-    lacunarity: f64,
-    pixel_size: u32,
+// How long the controls must be idle before a new generation job starts
+const REGENERATE_DEBOUNCE: Duration = Duration::from_millis(150);
+
+// Undo keeps this many snapshots, each taken once the controls have been idle for a while and nothing
+// is being dragged
+const MAX_UNDO: usize = 100;
+const UNDO_SETTLE: Duration = Duration::from_millis(500);
+
+// How often the progress bar refreshes while a job runs
+const PROGRESS_REPAINT: Duration = Duration::from_millis(50);
+
+// Scroll distance egui reports for one mouse wheel notch, and the share of a slider's range one notch or
+// arrow key press moves, normally and with Shift held
+const WHEEL_NOTCH: f32 = 50.0;
+const WHEEL_STEP: f64 = 0.01;
+const WHEEL_FINE_STEP: f64 = 0.001;
+const WHEEL_HOVER_ID: &str = "wheel_slider_hovered";
+
+// Elevation curve plot and point handle sizes
+const CURVE_EDITOR_SIZE: f32 = 160.0;
+const CURVE_HANDLE_SIZE: f32 = 10.0;
+
+// Gradient editor preview strip and stop handle sizes
+const GRADIENT_STRIP_HEIGHT: f32 = 24.0;
+const GRADIENT_HANDLE_SIZE: f32 = 10.0;
+
+// On-disk format for Save/Load Config and the settings kept between launches; missing fields fall back
+// to the defaults
+#[derive(Serialize, Deserialize)]
+struct SavedConfig {
+    #[serde(default)]
+    seed: Option<u32>,
+    #[serde(default)]
+    config: TerrainConfig,
+}
+
+impl SavedConfig {
+    // Compact JSON embedded in exported PNGs
+    fn embedded(config: &TerrainConfig, seed: u32) -> String {
+        let saved = SavedConfig { seed: Some(seed), config: config.clone() };
+        serde_json::to_string(&saved).expect("configs always serialize")
+    }
+
+    // Files and stored settings can hold anything, so bring the config within the limits the controls
+    // keep to before it is used; returns a note for each setting that had to change
+    fn validate(&mut self) -> Vec<String> {
+        self.config.sort_bands();
+        limit_config(&mut self.config)
+    }
 }
 
+// Clamp the size to MIN_DIMENSION..=MAX_DIMENSION and lower the supersampling to the largest factor the
+// size allows, like the Size fields and the Supersampling menu do, and keep the continent scale and world
+// span inside their slider ranges. Returns a note per setting changed.
+fn limit_config(config: &mut TerrainConfig) -> Vec<String> {
+    let mut notes = Vec::new();
+    for (name, side) in [("width", &mut config.width), ("height", &mut config.height)] {
+        let limited = (*side).clamp(MIN_DIMENSION, MAX_DIMENSION);
+        if limited != *side {
+            notes.push(format!("{} {} is outside {}..={}", name, side, MIN_DIMENSION, MAX_DIMENSION));
+            *side = limited;
+        }
+    }
+    let longest = config.width.max(config.height);
+    let requested = config.supersample.max(1);
+    let factor = SUPERSAMPLE_FACTORS
+        .into_iter()
+        .rev()
+        .find(|&factor| factor <= requested && longest * factor <= MAX_SUPERSAMPLED_DIMENSION)
+        .unwrap_or(1);
+    if factor != requested {
+        notes.push(format!("{}× supersampling isn't available at {}×{}", requested, config.width, config.height));
+    }
+    config.supersample = factor;
+    for (name, value, range) in [("continent scale", &mut config.continent_scale, CONTINENT_SCALE_RANGE), ("world span", &mut config.world_span, WORLD_SPAN_RANGE)] {
+        let limited = value.clamp(*range.start(), *range.end());
+        if limited != *value {
+            notes.push(format!("{} {} is outside {}..={}", name, value, range.start(), range.end()));
+            *value = limited;
+        }
+    }
+    notes
+}
+
+// Interface font loaded at startup, relative to the working directory
+const DEFAULT_FONT: &str = "src/fonts/OldLondon.ttf";
+
+// Labels for the two terrains in the split view
+const LAYER_NAMES: [&str; 2] = ["A", "B"];
+
 struct TerrainApp {
-    config: TerrainConfig,
-    terrain: egui::ColorImage,
-    seed: u32,
-    texture_handle: Option<egui::TextureHandle>,
+    // The terrain being edited, and a second one shown next to it in the split view
+    layers: [TerrainLayer; 2],
+    split_view: bool,
+    // Layer the overview, 3D view, keyboard shortcuts, file operations and batch export act on: the one
+    // last edited
+    active: usize,
+    export_path: String,
+    status: Option<String>,
+    config_path: String,
+    font_path: String,
+    // Batch Export window settings and the running batch, if any
+    batch_open: bool,
+    batch_start: u32,
+    batch_count: u32,
+    batch_dir: String,
+    batch: Option<BatchJob>,
+    // Seed Gallery window and the previews it shows
+    gallery_open: bool,
+    gallery: Option<Gallery>,
+    // Side of the square tiles Export Tiles slices the image into, in pixels
+    tile_size: u32,
+    // Pixels between the vertices Export OBJ writes
+    mesh_stride: u32,
+    // Biome Scatter places points on and the least distance between them, in pixels
+    scatter_biome: String,
+    scatter_spacing: f64,
+    clipboard: Option<arboard::Clipboard>,
+    // View transform for the preview, shared by both split panels: magnification and top-left corner in
+    // texture UV space
+    zoom: f32,
+    pan: egui::Vec2,
+    // Preview-only grid, with its cell size in terrain pixels
+    show_grid: bool,
+    grid_cell: u32,
+    // Preview-only arrows pointing down the steepest slope
+    show_flow: bool,
+    // Show a 2×2 repeat of the terrain so tileable seams can be checked; zoom and pan then act on the repeat
+    show_tiling: bool,
+    // 3D view window and its camera
+    show_3d: bool,
+    // Set by Capture Window until the screenshot arrives in `post_rendering`
+    capture_requested: bool,
+    orbit: Orbit,
 }
 
 impl eframe::App for TerrainApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut regenerate = false;
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.layers[0].saved_config());
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let mut edits = [Edits::default(), Edits::default()];
+        let active = self.active;
+
+        if ctx.input(|i| i.key_pressed(egui::Key::F11)) {
+            frame.set_fullscreen(!frame.info().window_info.fullscreen);
+        }
 
         // Set the background color
         let bg_color = Color32::from_rgb(218, 204, 158); // Light brown
@@ -36,151 +246,1207 @@ impl eframe::App for TerrainApp {
             ..Default::default()
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
+        // Space rerolls the seed while no widget has keyboard focus, so typing in a text field (or
+        // activating a focused button) doesn't also reroll
+        if ctx.memory(|m| m.focus().is_none()) && ctx.input(|i| i.key_pressed(egui::Key::Space)) {
+            edits[active].regenerate |= self.layers[active].reroll_seed();
+        }
+
+        // Text fields have their own undo, so only take the shortcuts while nothing is focused
+        if ctx.memory(|m| m.focus().is_none()) {
+            let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+            let redo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+            let layer = &mut self.layers[active];
+            if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+                edits[active].restored = layer.undo();
+            } else if ctx.input_mut(|i| i.consume_shortcut(&redo)) {
+                edits[active].restored = layer.redo();
+            }
+            edits[active].regenerate |= edits[active].restored;
+        }
+
+        // Dropped configs and exported PNGs load into the active terrain, like Load Config and Import from PNG
+        let dropped = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect::<Vec<_>>());
+        for path in dropped {
+            edits[active].regenerate |= self.open_dropped(ctx, &path);
+        }
+
+        self.show_batch_window(ctx);
+        if let Some(seed) = self.show_gallery_window(ctx) {
+            self.layers[active].use_seed(seed);
+            edits[active].regenerate = true;
+        }
+
+        let mut show_3d = self.show_3d;
+        egui::Window::new("3D View").open(&mut show_3d).default_size(egui::vec2(420.0, 320.0)).show(ctx, |ui| {
+            ui.label("Drag to orbit.");
+            let layer = &self.layers[active];
+            preview3d::show(ui, &mut self.orbit, &layer.maps, &layer.terrain);
+        });
+        self.show_3d = show_3d;
+
+        egui::SidePanel::right("overview").resizable(false).show(ctx, |ui| {
+            ui.heading("Overview");
+            if self.split_view {
+                ui.label(format!("Terrain {}", LAYER_NAMES[active]));
+            }
+            self.show_minimap(ui);
+            ui.separator();
+            ui.label("Elevation");
+            self.show_histogram(ui);
+            ui.separator();
+            ui.label("Legend");
+            self.show_legend(ui);
+            ui.separator();
+            ui.label("Coverage");
+            self.show_biome_shares(ui);
+        });
+
+        // The parameters scroll in their own panel so the preview can take all the remaining space
+        let controls_width = if self.split_view { 2.0 * CONTROLS_WIDTH } else { CONTROLS_WIDTH };
+        egui::SidePanel::left("controls").default_width(controls_width).show(ctx, |ui| {
             ui.heading("Terrain Generator");
             ui.separator();
+            egui::ScrollArea::vertical().enable_scrolling(!wheel_slider_hovered(ctx)).show(ui, |ui| {
+                if self.split_view {
+                    ui.columns(2, |columns| {
+                        for (index, ui) in columns.iter_mut().enumerate() {
+                            ui.push_id(index, |ui| {
+                                ui.strong(format!("Terrain {}", LAYER_NAMES[index]));
+                                edits[index] = self.layers[index].show_controls(ui);
+                            });
+                        }
+                    });
+                } else {
+                    edits[0] = self.layers[0].show_controls(ui);
+                }
+            });
+        });
+        if let Some(edited) = (0..2).find(|&index| edits[index].regenerate || edits[index].recolor) {
+            self.active = edited;
+        }
+        let active = self.active;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+
+            ui.horizontal(|ui| {
+                ui.label("Output");
+                let hint = self.default_export_path().display().to_string();
+                ui.add(egui::TextEdit::singleline(&mut self.export_path).hint_text(hint));
+                if ui.button("Save PNG").on_hover_text("Save the terrain at full resolution as the view mode shows it, without overlays").clicked() {
+                    let path = self.export_target();
+                    let result = self.export_png(&path);
+                    self.report("Saved", &path, result);
+                }
+                if ui.button("Import from PNG").on_hover_text("Restore the parameters saved in an exported PNG").clicked() {
+                    let path = self.export_target();
+                    edits[active].regenerate |= self.open_png(ctx, &path);
+                }
+                if ui.button("Import Heightmap").on_hover_text("Color the grayscale image at this path instead of generating heights").clicked() {
+                    let path = self.export_target();
+                    let result = load_heightmap(&path).map(|heightmap| self.layer_mut().use_heightmap(heightmap));
+                    edits[active].regenerate |= result.is_ok();
+                    self.report("Imported heights from", &path, result);
+                }
+                if ui.button("Save Heightmap").clicked() {
+                    let path = self.suffixed_target("height");
+                    let result = self.export_heightmap(&path);
+                    self.report("Saved", &path, result);
+                }
+                if ui.button("Save Normal Map").clicked() {
+                    let path = self.suffixed_target("normal");
+                    let result = self.export_normal_map(&path);
+                    self.report("Saved", &path, result);
+                }
+                if ui.button("Copy Image").clicked() {
+                    self.status = Some(match self.copy_to_clipboard() {
+                        Ok(()) => "Copied terrain to the clipboard".to_owned(),
+                        Err(err) => format!("Clipboard image copy isn't available here: {}", err),
+                    });
+                }
+                let hover = format!(
+                    "Save the window as it is drawn, with the controls, overlays and preview scaling, to {}. Save PNG writes the clean terrain instead.",
+                    self.suffixed_target("window").display()
+                );
+                if ui.button("Capture Window").on_hover_text(hover).clicked() {
+                    self.capture_requested = true;
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Tiles");
+                ui.add(egui::DragValue::new(&mut self.tile_size).clamp_range(MIN_TILE_SIZE..=MAX_DIMENSION).suffix(" px"));
+                let dir = self.tiles_dir();
+                if ui.button("Export Tiles").on_hover_text(format!("Write tile_<x>_<y>.png and tiles.json into {}", dir.display())).clicked() {
+                    let result = self.export_tiles(&dir);
+                    self.status = Some(match result {
+                        Ok(count) => format!("Saved {} tiles to {}", count, dir.display()),
+                        Err(err) => format!("Error with {}: {}", dir.display(), err),
+                    });
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Mesh");
+                ui.add(egui::DragValue::new(&mut self.mesh_stride).clamp_range(1..=64).prefix("every ").suffix(" px"));
+                ui.add(egui::DragValue::new(&mut self.orbit.exaggeration).clamp_range(0.1..=5.0).speed(0.01).prefix("height ×"))
+                    .on_hover_text("Vertical exaggeration, shared with the 3D view");
+                let path = self.export_target().with_extension("obj");
+                if ui.button("Export OBJ").on_hover_text(format!("Write {} with its material and color texture", path.display())).clicked() {
+                    let result = self.export_obj(&path);
+                    self.report("Saved", &path, result);
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Config");
+                ui.add(egui::TextEdit::singleline(&mut self.config_path).desired_width(160.0));
+                let path = PathBuf::from(self.config_path.trim());
+                if ui.button("Save Config").clicked() {
+                    let result = self.save_config(&path);
+                    self.report("Saved", &path, result);
+                }
+                if ui.button("Load Config").clicked() {
+                    let result = self.load_config(&path);
+                    edits[active].regenerate |= result.is_ok();
+                    self.report_loaded("Loaded", &path, result);
+                }
+                if ui.button("Batch Export…").clicked() {
+                    self.batch_open = true;
+                }
+                if ui.button("Seed Gallery…").clicked() {
+                    self.gallery_open = true;
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Font");
+                ui.add(egui::TextEdit::singleline(&mut self.font_path).desired_width(160.0));
+                if ui.button("Load Font").on_hover_text("Use a .ttf or .otf file for the interface").clicked() {
+                    let path = PathBuf::from(self.font_path.trim());
+                    let result = install_font(ctx, &path);
+                    self.report("Loaded", &path, result);
+                }
+            });
 
-            regenerate |= ui.add(egui::Slider::new(&mut self.config.scale, 1.0..=100.0).text("Scale")).changed();
-            regenerate |= ui.add(egui::Slider::new(&mut self.config.octaves, 1..=8).text("Octaves")).changed();
-            regenerate |= ui.add(egui::Slider::new(&mut self.config.persistence, 0.0..=1.0).text("Persistence")).changed();
-            regenerate |= ui.add(egui::Slider::new(&mut self.config.lacunarity, 1.0..=4.0).text("Lacunarity")).changed();
-            regenerate |= ui.add(egui::Slider::new(&mut self.config.pixel_size, 1..=16).text("Pixel Size")).changed();
+            ui.horizontal(|ui| {
+                let layer = &mut self.layers[active];
+                let ready = layer.maps.size == layer.terrain.size && !layer.maps.heights.is_empty();
+                if ui
+                    .add_enabled(ready, egui::Button::new("Find Spawn"))
+                    .on_hover_text("Pin the middle of the largest grass region, or of the largest land region without grass")
+                    .clicked()
+                {
+                    layer.spawn = spawn::find_spawn(&layer.config, &layer.maps);
+                    if layer.spawn.is_none() {
+                        self.status = Some("No land above the sea level to spawn on".to_owned());
+                    }
+                }
+                if let Some([x, y]) = layer.spawn {
+                    // Read-only, but still selectable so the coordinate can be copied
+                    let coordinate = format!("{}, {}", x, y);
+                    ui.add(egui::TextEdit::singleline(&mut coordinate.as_str()).desired_width(90.0));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Scatter");
+                let layer = &mut self.layers[active];
+                egui::ComboBox::from_id_source("scatter_biome").selected_text(self.scatter_biome.as_str()).show_ui(ui, |ui| {
+                    for (name, _, _) in &layer.biome_shares {
+                        ui.selectable_value(&mut self.scatter_biome, name.clone(), name.as_str());
+                    }
+                });
+                ui.add(egui::DragValue::new(&mut self.scatter_spacing).clamp_range(2.0..=256.0).prefix("every ").suffix(" px"));
+                let ready = layer.maps.size == layer.terrain.size && !layer.maps.heights.is_empty();
+                if ui.add_enabled(ready, egui::Button::new("Place")).on_hover_text("Evenly spaced points on the chosen biome, the same for every run of a seed").clicked() {
+                    layer.scatter = scatter::scatter(&layer.config, &layer.maps, &self.scatter_biome, self.scatter_spacing);
+                    self.status = Some(format!("Placed {} points on {}", layer.scatter.len(), self.scatter_biome));
+                }
+                let path = self.suffixed_target("points").with_extension("json");
+                let layer = &self.layers[active];
+                if ui.add_enabled(!layer.scatter.is_empty(), egui::Button::new("Export Points")).clicked() {
+                    let result = save_points(&layer.scatter, &self.scatter_biome, self.scatter_spacing, layer.maps.seed, &path);
+                    self.report("Saved", &path, result);
+                }
+            });
 
-            if ui.button("New Seed").clicked() {
-                self.seed = rand::thread_rng().gen();
-                regenerate = true;
+            if let Some(status) = &self.status {
+                ui.label(status);
             }
 
-            if let Some(texture_handle) = self.texture_handle.as_ref() {
-                ui.image(texture_handle, texture_handle.size_vec2());
+            ui.horizontal(|ui| {
+                if ui.button("Reset View").clicked() {
+                    self.reset_view();
+                }
+                let layer = &mut self.layers[active];
+                let filter = layer.config.texture_filter;
+                egui::ComboBox::from_id_source("texture_filter")
+                    .selected_text(if filter == egui::TextureFilter::Nearest { "Nearest" } else { "Linear" })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut layer.config.texture_filter, egui::TextureFilter::Nearest, "Nearest");
+                        ui.selectable_value(&mut layer.config.texture_filter, egui::TextureFilter::Linear, "Linear");
+                    });
+                let view_mode = layer.config.view_mode;
+                egui::ComboBox::from_id_source("view_mode").selected_text(view_mode.label()).show_ui(ui, |ui| {
+                    for mode in ViewMode::ALL {
+                        ui.selectable_value(&mut layer.config.view_mode, mode, mode.label());
+                    }
+                });
+                // Only the texture needs re-uploading; the image itself is unchanged
+                if layer.config.texture_filter != filter || layer.config.view_mode != view_mode {
+                    layer.update_texture(ctx);
+                }
+                ui.checkbox(&mut self.show_3d, "3D View");
+                ui.checkbox(&mut self.show_tiling, "Show Tiling");
+                // Grid coordinates only make sense over a single copy
+                ui.add_enabled(!self.show_tiling, egui::Checkbox::new(&mut self.show_grid, "Grid"));
+                ui.add_enabled(self.show_grid && !self.show_tiling, egui::DragValue::new(&mut self.grid_cell).clamp_range(4..=1024).suffix(" px"));
+                ui.add_enabled(!self.show_tiling, egui::Checkbox::new(&mut self.show_flow, "Flow"))
+                    .on_hover_text("Arrows down the steepest slope, longer where it is steeper");
+                if ui.checkbox(&mut self.split_view, "Split View").on_hover_text("Compare two independently configured terrains").changed() {
+                    self.toggle_split_view(ctx);
+                }
+                for (index, layer) in self.layers.iter().enumerate().take(if self.split_view { 2 } else { 1 }) {
+                    let prefix = if self.split_view { format!("{}: ", LAYER_NAMES[index]) } else { String::new() };
+                    if let Some(progress) = layer.progress() {
+                        ui.add(egui::ProgressBar::new(progress).show_percentage());
+                    } else if let Some((kind, elapsed)) = layer.last_timing {
+                        ui.label(format!("{}{} in {} ms", prefix, kind, elapsed.as_millis()));
+                    }
+                }
+            });
+            if self.split_view {
+                ui.columns(2, |columns| {
+                    for (index, ui) in columns.iter_mut().enumerate() {
+                        self.show_preview(ui, index);
+                    }
+                });
+            } else {
+                self.show_preview(ui, 0);
             }
         });
 
-        if regenerate {
-            self.regenerate_terrain();
-            self.update_texture(ctx);
+        for (layer, edits) in self.layers.iter_mut().zip(edits) {
+            layer.finish_frame(ctx, edits);
+        }
+        if self.capture_requested {
+            frame.request_screenshot();
         }
     }
+
+    // The screenshot requested by Capture Window, taken once this frame has been drawn
+    fn post_rendering(&mut self, _window_size_px: [u32; 2], frame: &eframe::Frame) {
+        let Some(screenshot) = frame.screenshot() else {
+            return;
+        };
+        self.capture_requested = false;
+        let path = self.suffixed_target("window");
+        let result = save_png(&screenshot, None, &path);
+        self.report("Captured the window to", &path, result);
+    }
 }
 
 impl TerrainApp {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // Load custom font
-        let mut fonts = FontDefinitions::default();
-        
-        // Load your custom font file
-        let font_data = std::fs::read("src/fonts/OldLondon.ttf").expect("Failed to read font file");
-        
-        // Add the font to FontDefinitions
-        fonts.font_data.insert("my_font".to_owned(), egui::FontData::from_owned(font_data));
-        
-        // Set the font as the default for various text styles
-        fonts.families.get_mut(&FontFamily::Proportional).unwrap()
-            .insert(0, "my_font".to_owned());
-        fonts.families.get_mut(&FontFamily::Monospace).unwrap()
-            .push("my_font".to_owned());
-
-        // Set the font
-        cc.egui_ctx.set_fonts(fonts);
-
-        let config = TerrainConfig {
-            width: 512,
-            height: 512,
-            scale: 50.0,
-            octaves: 6,
-            persistence: 0.5,
-            lacunarity: 2.0,
-            pixel_size: 1,
+        // Without the bundled font the UI keeps egui's default fonts
+        let font_path = DEFAULT_FONT.to_owned();
+        let status = install_font(&cc.egui_ctx, Path::new(&font_path))
+            .err()
+            .map(|err| format!("Using the default font, {} couldn't be loaded: {}", font_path, err));
+
+        // Start from the last session's settings, kept within the limits like a loaded config; missing or
+        // unreadable storage falls back to the defaults
+        let saved: Option<SavedConfig> = cc.storage.and_then(|storage| eframe::get_value(storage, eframe::APP_KEY));
+        let (config, seed, notes) = match saved {
+            Some(mut saved) => {
+                let notes = saved.validate();
+                (saved.config, saved.seed.unwrap_or_else(new_seed), notes)
+            }
+            None => (TerrainConfig::default(), new_seed(), Vec::new()),
         };
-        let seed = rand::thread_rng().gen();
+        let status = status.or_else(|| (!notes.is_empty()).then(|| format!("Adjusted the last session's settings: {}", notes.join("; "))));
         let mut app = Self {
-            config,
-            terrain: egui::ColorImage::new([256, 256], Color32::BLACK),
-            seed,
-            texture_handle: None,
+            layers: [TerrainLayer::new(config.clone(), seed), TerrainLayer::new(config, seed)],
+            split_view: false,
+            active: 0,
+            export_path: String::new(),
+            status,
+            config_path: "terrain_config.json".to_owned(),
+            font_path,
+            batch_open: false,
+            batch_start: seed,
+            batch_count: 10,
+            batch_dir: "batch".to_owned(),
+            batch: None,
+            gallery_open: true,
+            gallery: None,
+            tile_size: 512,
+            mesh_stride: 4,
+            scatter_biome: "Grass".to_owned(),
+            scatter_spacing: 16.0,
+            clipboard: None,
+            zoom: 1.0,
+            pan: egui::Vec2::ZERO,
+            show_grid: false,
+            grid_cell: 64,
+            show_flow: false,
+            show_tiling: false,
+            show_3d: false,
+            capture_requested: false,
+            orbit: Orbit::default(),
         };
-        app.regenerate_terrain();
-        app.update_texture(&cc.egui_ctx);
+        app.layers[0].regenerate_terrain(&cc.egui_ctx);
+        app.gallery = Some(Gallery::spawn(&cc.egui_ctx, &app.layers[0].config, (0..GALLERY_SEEDS).collect()));
         app
     }
 
-    fn regenerate_terrain(&mut self) {
-        let perlin = Perlin::new(self.seed);
-        let width = self.config.width;
-        let height = self.config.height;
-        let scale = self.config.scale;
-        let octaves = self.config.octaves;
-        let persistence = self.config.persistence;
-        let lacunarity = self.config.lacunarity;
-
-        let pixels: Vec<Color32> = (0..height)
-            .flat_map(|y| {
-                (0..width).map(move |x| {
-                    let nx = x as f64 / width as f64 - 0.5;
-                    let ny = y as f64 / height as f64 - 0.5;
-
-                    let mut noise_value = 0.0;
-                    let mut amplitude = 1.0;
-                    let mut frequency = 1.0;
-
-                    for _ in 0..octaves {
-                        let sample_x = nx * frequency * scale;
-                        let sample_y = ny * frequency * scale;
-                        noise_value += perlin.get([sample_x, sample_y]) * amplitude;
-
-                        amplitude *= persistence;
-                        frequency *= lacunarity;
+    // The second terrain starts as a copy of the first the first time the split view opens, and keeps its
+    // own settings after that. Closing the split view goes back to editing the first terrain.
+    fn toggle_split_view(&mut self, ctx: &egui::Context) {
+        if !self.split_view {
+            self.active = 0;
+            return;
+        }
+        if !self.layers[1].is_started() {
+            let first = &self.layers[0];
+            self.layers[1] = TerrainLayer::new(first.config.clone(), first.seed);
+            self.layers[1].regenerate_terrain(ctx);
+        }
+    }
+
+    fn layer(&self) -> &TerrainLayer {
+        &self.layers[self.active]
+    }
+
+    fn layer_mut(&mut self) -> &mut TerrainLayer {
+        &mut self.layers[self.active]
+    }
+
+    // Settings for exporting consecutive seeds with the current config, and the progress of a running batch
+    fn show_batch_window(&mut self, ctx: &egui::Context) {
+        if let Some(result) = self.batch.as_ref().and_then(BatchJob::poll) {
+            self.batch = None;
+            let dir = self.batch_dir.trim();
+            self.status = Some(match result {
+                Ok(written) => format!("Exported {} maps to {}", written, dir),
+                Err(err) => format!("Batch export failed: {}", err),
+            });
+        }
+
+        let mut open = self.batch_open;
+        egui::Window::new("Batch Export").open(&mut open).resizable(false).show(ctx, |ui| {
+            let idle = self.batch.is_none();
+            ui.add_enabled_ui(idle, |ui| {
+                egui::Grid::new("batch_settings").show(ui, |ui| {
+                    ui.label("Start seed");
+                    ui.add(egui::DragValue::new(&mut self.batch_start));
+                    ui.end_row();
+                    ui.label("Count");
+                    ui.add(egui::DragValue::new(&mut self.batch_count).clamp_range(1..=10_000));
+                    ui.end_row();
+                    ui.label("Folder");
+                    ui.text_edit_singleline(&mut self.batch_dir);
+                    ui.end_row();
+                });
+            });
+            match &self.batch {
+                Some(batch) => {
+                    ui.add(egui::ProgressBar::new(batch.fraction()).show_percentage());
+                    if ui.button("Cancel").clicked() {
+                        batch.cancel();
+                    }
+                }
+                None => {
+                    if ui.button("Export").clicked() {
+                        let dir = PathBuf::from(self.batch_dir.trim());
+                        self.batch = Some(BatchJob::spawn(ctx, self.layer().config.clone(), self.batch_start, self.batch_count, dir));
+                    }
+                }
+            }
+        });
+        self.batch_open = open;
+    }
+
+    // Previews of a handful of seeds with the active terrain's parameters; returns the seed clicked, if any.
+    // Closing the window stops any previews still being generated.
+    fn show_gallery_window(&mut self, ctx: &egui::Context) -> Option<u32> {
+        if !self.gallery_open {
+            self.gallery = None;
+            return None;
+        }
+        let mut picked = None;
+        let mut open = self.gallery_open;
+        egui::Window::new("Seed Gallery").open(&mut open).resizable(false).show(ctx, |ui| {
+            ui.label("Click a preview to use its seed.");
+            if ui.button("Re-roll").on_hover_text("Preview other seeds with the current parameters").clicked() {
+                let start = new_seed();
+                let seeds = (0..GALLERY_SEEDS).map(|offset| start.wrapping_add(offset)).collect();
+                self.gallery = Some(Gallery::spawn(ctx, &self.layer().config, seeds));
+            }
+            let config = &self.layers[self.active].config;
+            let gallery = self.gallery.get_or_insert_with(|| Gallery::spawn(ctx, config, (0..GALLERY_SEEDS).collect()));
+            gallery.poll(ctx);
+            egui::Grid::new("gallery").show(ui, |ui| {
+                for (slot, (&seed, texture)) in gallery.seeds.iter().zip(&gallery.textures).enumerate() {
+                    ui.vertical(|ui| {
+                        match texture {
+                            Some(texture) => {
+                                if ui.add(egui::ImageButton::new(texture.id(), texture.size_vec2())).clicked() {
+                                    picked = Some(seed);
+                                }
+                            }
+                            None => {
+                                ui.spinner();
+                            }
+                        }
+                        ui.label(seed.to_string());
+                    });
+                    if (slot + 1) % GALLERY_COLUMNS == 0 {
+                        ui.end_row();
                     }
+                }
+            });
+        });
+        self.gallery_open = open;
+        picked
+    }
+
+    fn reset_view(&mut self) {
+        self.zoom = 1.0;
+        self.pan = egui::Vec2::ZERO;
+    }
+
+    // Portion of the texture currently visible, in UV coordinates
+    fn view_uv_rect(&self) -> egui::Rect {
+        egui::Rect::from_min_size(self.pan.to_pos2(), egui::Vec2::splat(1.0 / self.zoom))
+    }
+
+    // Draw a layer with the current zoom/pan; scrolling zooms around the cursor and dragging pans
+    fn show_preview(&mut self, ui: &mut egui::Ui, index: usize) {
+        let Some(texture_handle) = self.layers[index].texture_handle.as_ref() else {
+            return;
+        };
+        let texture_id = texture_handle.id();
+        // Scale the map to fill the space left in the panel, keeping its aspect ratio
+        let size = texture_handle.size_vec2();
+        let available = ui.available_size();
+        let mut fit = (available.x / size.x).min(available.y / size.y).max(MIN_PREVIEW_SCALE);
+        // Pixels and blocks only stay crisp while each covers a whole number of physical screen pixels, which
+        // on a HiDPI display is not the same as a whole number of points, so the fit and zoom snap to that.
+        // Maps too large for even one screen pixel per block are left as they are. Exports are unaffected
+        // and always written at the generated resolution.
+        let copies = if self.show_tiling { 2.0 } else { 1.0 };
+        let block = self.layers[index].config.pixel_size.max(1) as f32;
+        let block_pixels = block * ui.ctx().pixels_per_point() / copies;
+        let crisp = (block_pixels * fit).floor() >= 1.0;
+        if crisp {
+            fit = (block_pixels * fit).floor() / block_pixels;
+        }
+        let (rect, response) = ui.allocate_exact_size(size * fit, egui::Sense::drag());
+        // Start on a physical pixel too, or every texel edge would straddle two
+        let rect = egui::Rect::from_min_size(ui.painter().round_pos_to_pixels(rect.min), rect.size());
+
+        if response.dragged() {
+            self.pan -= response.drag_delta() / rect.size() / self.zoom;
+        }
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ui.input(|i| i.scroll_delta.y);
+            if scroll != 0.0 {
+                // Keep the texel under the cursor fixed while the zoom changes
+                let cursor = (pointer - rect.min) / rect.size();
+                let anchor = self.pan + cursor / self.zoom;
+                let mut zoom = (self.zoom * (scroll * ZOOM_SPEED).exp()).clamp(1.0, MAX_ZOOM);
+                if crisp {
+                    // Step by at least one screen pixel per block so small scrolls aren't rounded away
+                    let unit = block_pixels * fit;
+                    let current = (self.zoom * unit).round();
+                    let mut next = (zoom * unit).round();
+                    if next == current {
+                        next = current + scroll.signum();
+                    }
+                    zoom = (next / unit).clamp(1.0, MAX_ZOOM);
+                }
+                self.zoom = zoom;
+                self.pan = anchor - cursor / self.zoom;
+            }
+        }
+        let max_pan = 1.0 - 1.0 / self.zoom;
+        self.pan = self.pan.clamp(egui::Vec2::ZERO, egui::Vec2::splat(max_pan));
+
+        if self.show_tiling {
+            self.paint_tiled(ui, rect, texture_id);
+        } else {
+            ui.painter().image(texture_id, rect, self.view_uv_rect(), Color32::WHITE);
+            if self.show_grid {
+                self.paint_grid(ui, rect, self.layers[index].terrain.size);
+            }
+            if self.show_flow && self.layers[index].maps.size == self.layers[index].terrain.size {
+                self.paint_flow(ui, rect, &self.layers[index].maps);
+            }
+            self.paint_markers(ui, rect, &self.layers[index].scatter, self.layers[index].maps.size);
+            if let Some(spawn) = self.layers[index].spawn {
+                self.paint_pin(ui, rect, spawn, self.layers[index].maps.size);
+            }
+        }
+
+        if let Some(pointer) = response.hover_pos() {
+            let layer = &self.layers[index];
+            if let Some(index) = self.pixel_at(layer, rect, pointer) {
+                let height = layer.maps.heights[index];
+                let biome = layer.config.pixel_biome(&layer.maps, index).map_or("", |biome| biome.name);
+                response.on_hover_text(format!("Height {:.3}\n{}", height, biome));
+            }
+        }
+    }
+
+    // Draw the visible part of each of the four copies; the view rect is in the repeat's 0..1 space, where
+    // every copy covers one quadrant
+    fn paint_tiled(&self, ui: &egui::Ui, rect: egui::Rect, texture_id: egui::TextureId) {
+        let view = self.view_uv_rect();
+        for (column, row) in [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)] {
+            let tile = egui::Rect::from_min_size(egui::pos2(column, row), egui::Vec2::splat(0.5));
+            let visible = tile.intersect(view);
+            if visible.width() <= 0.0 || visible.height() <= 0.0 {
+                continue;
+            }
+            let to_screen = |pos: egui::Pos2| rect.min + (pos - view.min) / view.size() * rect.size();
+            let to_uv = |pos: egui::Pos2| ((pos - tile.min) * 2.0).to_pos2();
+            let screen = egui::Rect::from_min_max(to_screen(visible.min), to_screen(visible.max));
+            let uv = egui::Rect::from_min_max(to_uv(visible.min), to_uv(visible.max));
+            ui.painter().image(texture_id, screen, uv, Color32::WHITE);
+        }
+    }
+
+    // The whole terrain with the region shown in the preview outlined; clicking or dragging recenters the preview there
+    fn show_minimap(&mut self, ui: &mut egui::Ui) {
+        let Some(minimap_handle) = self.layer().minimap_handle.as_ref() else {
+            return;
+        };
+        let texture_id = minimap_handle.id();
+        let (rect, response) = ui.allocate_exact_size(minimap_handle.size_vec2(), egui::Sense::click_and_drag());
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let center = (pointer - rect.min) / rect.size();
+            let max_pan = 1.0 - 1.0 / self.zoom;
+            self.pan = (center - egui::Vec2::splat(0.5 / self.zoom)).clamp(egui::Vec2::ZERO, egui::Vec2::splat(max_pan));
+        }
+
+        let full = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        ui.painter().image(texture_id, rect, full, Color32::WHITE);
+        let uv = self.view_uv_rect();
+        let view = egui::Rect::from_min_max(rect.min + uv.min.to_vec2() * rect.size(), rect.min + uv.max.to_vec2() * rect.size());
+        ui.painter().rect_stroke(view, 0.0, egui::Stroke::new(1.5, Color32::RED));
+    }
+
+    // Bar chart of the height distribution, with a line at each band threshold in the band's color and a
+    // thicker one at the sea level
+    fn show_histogram(&self, ui: &mut egui::Ui) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(MINIMAP_SIZE as f32, HISTOGRAM_HEIGHT), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, Color32::from_black_alpha(30));
+        let layer = self.layer();
+        let tallest = layer.histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let bar_width = rect.width() / layer.histogram.len().max(1) as f32;
+        for (bin, &count) in layer.histogram.iter().enumerate() {
+            let left = rect.left() + bin as f32 * bar_width;
+            let top = rect.bottom() - count as f32 / tallest * rect.height();
+            let bar = egui::Rect::from_min_max(egui::pos2(left, top), egui::pos2(left + bar_width, rect.bottom()));
+            painter.rect_filled(bar, 0.0, Color32::from_gray(60));
+        }
+        let x_at = |height: f64| rect.left() + height.clamp(0.0, 1.0) as f32 * rect.width();
+        for band in &layer.config.bands {
+            painter.vline(x_at(band.threshold), rect.y_range(), egui::Stroke::new(1.0, band.color));
+        }
+        painter.vline(x_at(layer.config.sea_level), rect.y_range(), egui::Stroke::new(2.0, Color32::from_rgb(0, 60, 160)));
+    }
+
+    // One row per band: the color it is drawn with, its name and the heights it covers
+    fn show_legend(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("legend").num_columns(3).show(ui, |ui| {
+            let mut lower = 0.0;
+            let config = &self.layer().config;
+            let last = config.bands.len().saturating_sub(1);
+            for (index, band) in config.bands.iter().enumerate() {
+                let (swatch, _) = ui.allocate_exact_size(egui::Vec2::splat(LEGEND_SWATCH_SIZE), egui::Sense::hover());
+                ui.painter().rect_filled(swatch, 2.0, config.band_color(band));
+                ui.label(&band.name);
+                // The last band also covers everything above it
+                if index == last {
+                    ui.label(format!("{:.2}+", lower));
+                } else {
+                    ui.label(format!("{:.2}–{:.2}", lower, band.threshold));
+                }
+                ui.end_row();
+                lower = band.threshold;
+            }
+        });
+    }
+
+    // A bar split by how much of the map each biome covers, then the same shares as percentages
+    fn show_biome_shares(&self, ui: &mut egui::Ui) {
+        let shares = &self.layer().biome_shares;
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(MINIMAP_SIZE as f32, LEGEND_SWATCH_SIZE), egui::Sense::hover());
+        let mut left = rect.left();
+        for (_, color, share) in shares {
+            let right = left + *share as f32 * rect.width();
+            ui.painter().rect_filled(egui::Rect::from_x_y_ranges(left..=right, rect.y_range()), 0.0, *color);
+            left = right;
+        }
+        egui::Grid::new("biome_shares").num_columns(3).show(ui, |ui| {
+            for (name, color, share) in shares {
+                let (swatch, _) = ui.allocate_exact_size(egui::Vec2::splat(LEGEND_SWATCH_SIZE), egui::Sense::hover());
+                ui.painter().rect_filled(swatch, 2.0, *color);
+                ui.label(name);
+                ui.label(format!("{:.1}%", share * 100.0));
+                ui.end_row();
+            }
+        });
+    }
 
-                    noise_value = (noise_value + 1.0) / 2.0;
-                    Self::get_terrain_color(noise_value)
-                })
-            })
-            .collect();
-
-        self.terrain = egui::ColorImage::from_rgba_unmultiplied(
-            [width as _, height as _],
-            &pixels.iter().flat_map(|c| c.to_array()).collect::<Vec<u8>>(),
-        );
-    }
-
-    fn update_texture(&mut self, ctx: &egui::Context) {
-        self.texture_handle = Some(ctx.load_texture(
-            "terrain",
-            self.terrain.clone(),
-            egui::TextureOptions::NEAREST,
-        ));
-    }
-
-    fn get_terrain_color(height: f64) -> Color32 {
-        let color = match height {
-            h if h < 0.3 => [0, 0, 255],    // Deep water
-            h if h < 0.4 => [65, 105, 225], // Water
-            h if h < 0.5 => [210, 180, 140], // Sand
-            h if h < 0.7 => [34, 139, 34],  // Grass
-            h if h < 0.8 => [139, 69, 19],  // Mountain
-            _ => [255, 255, 255],           // Snow
+    // Grid lines every `grid_cell` terrain pixels, following the zoom and pan, labelled with the terrain
+    // coordinates of each intersection once the cells are large enough on screen to fit the text
+    fn paint_grid(&self, ui: &egui::Ui, rect: egui::Rect, [width, height]: [usize; 2]) {
+        let painter = ui.painter_at(rect);
+        let stroke = egui::Stroke::new(1.0, Color32::from_black_alpha(140));
+        let uv = self.view_uv_rect();
+        let cell = self.grid_cell.max(1) as usize;
+        let to_screen = |x: usize, y: usize| {
+            let texel = egui::vec2(x as f32 / width as f32, y as f32 / height as f32);
+            rect.min + (texel - uv.min.to_vec2()) / uv.size() * rect.size()
         };
-        Self::quantize_color(color, 1) // Assuming pixel_size is 1 for simplicity
+        let spacing = to_screen(cell, cell) - to_screen(0, 0);
+        let labelled = spacing.x.min(spacing.y) >= GRID_LABEL_SPACING;
+
+        let visible = |extent: usize, min: f32, max: f32| {
+            let first = (min * extent as f32) as usize / cell;
+            let last = ((max * extent as f32).ceil() as usize).min(extent) / cell;
+            (first..=last).map(move |line| line * cell)
+        };
+        for x in visible(width, uv.min.x, uv.max.x) {
+            let top = to_screen(x, 0);
+            painter.vline(top.x, rect.y_range(), stroke);
+        }
+        for y in visible(height, uv.min.y, uv.max.y) {
+            let left = to_screen(0, y);
+            painter.hline(rect.x_range(), left.y, stroke);
+            if labelled {
+                for x in visible(width, uv.min.x, uv.max.x) {
+                    painter.text(
+                        to_screen(x, y) + egui::vec2(2.0, 1.0),
+                        egui::Align2::LEFT_TOP,
+                        format!("{},{}", x, y),
+                        egui::FontId::monospace(9.0),
+                        Color32::BLACK,
+                    );
+                }
+            }
+        }
+    }
+
+    // An arrow every FLOW_ARROW_SPACING points pointing downhill, from the same height gradient hillshading
+    // and normal maps use, with its length growing with the slope up to FLOW_FULL_SLOPE
+    fn paint_flow(&self, ui: &egui::Ui, rect: egui::Rect, maps: &TerrainMaps) {
+        let painter = ui.painter_at(rect);
+        let stroke = egui::Stroke::new(1.5, Color32::from_black_alpha(200));
+        let uv = self.view_uv_rect();
+        let [width, height] = maps.size;
+        let columns = (rect.width() / FLOW_ARROW_SPACING).floor().max(1.0) as usize;
+        let rows = (rect.height() / FLOW_ARROW_SPACING).floor().max(1.0) as usize;
+        for row in 0..rows {
+            for column in 0..columns {
+                let center = rect.min + egui::vec2((column as f32 + 0.5) * rect.width() / columns as f32, (row as f32 + 0.5) * rect.height() / rows as f32);
+                let texel = uv.min + (center - rect.min) / rect.size() * uv.size();
+                let x = ((texel.x * width as f32) as usize).min(width - 1);
+                let y = ((texel.y * height as f32) as usize).min(height - 1);
+                let [dx, dy] = maps.gradient(x, y);
+                let slope = (dx * dx + dy * dy).sqrt();
+                let length = (slope / FLOW_FULL_SLOPE).min(1.0) as f32 * FLOW_ARROW_SPACING * 0.8;
+                if length < 2.0 {
+                    continue;
+                }
+                let downhill = egui::vec2(-dx as f32, -dy as f32).normalized() * length;
+                painter.arrow(center - downhill / 2.0, downhill, stroke);
+            }
+        }
+    }
+
+    // A dot on each scattered point that is in view
+    fn paint_markers(&self, ui: &egui::Ui, rect: egui::Rect, points: &[[usize; 2]], [width, height]: [usize; 2]) {
+        let painter = ui.painter_at(rect);
+        let uv = self.view_uv_rect();
+        for &[x, y] in points {
+            let texel = egui::vec2((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+            let point = rect.min + (texel - uv.min.to_vec2()) / uv.size() * rect.size();
+            if rect.contains(point) {
+                painter.circle(point, MARKER_RADIUS, Color32::from_rgb(250, 240, 200), egui::Stroke::new(1.0, Color32::BLACK));
+            }
+        }
+    }
+
+    // Mark a map pixel with a pin whose point sits on the pixel's center
+    fn paint_pin(&self, ui: &egui::Ui, rect: egui::Rect, [x, y]: [usize; 2], [width, height]: [usize; 2]) {
+        let uv = self.view_uv_rect();
+        let texel = egui::vec2((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+        let point = rect.min + (texel - uv.min.to_vec2()) / uv.size() * rect.size();
+        if !rect.contains(point) {
+            return;
+        }
+        let painter = ui.painter_at(rect);
+        let head = point - egui::vec2(0.0, PIN_HEIGHT);
+        painter.line_segment([point, head], egui::Stroke::new(2.0, Color32::BLACK));
+        painter.circle(head, PIN_RADIUS, Color32::from_rgb(220, 30, 30), egui::Stroke::new(1.5, Color32::BLACK));
+    }
+
+    // Convert a screen position over the preview into the index of the terrain pixel beneath it
+    fn pixel_at(&self, layer: &TerrainLayer, rect: egui::Rect, pointer: egui::Pos2) -> Option<usize> {
+        if !rect.contains(pointer) || layer.maps.size != layer.terrain.size {
+            return None;
+        }
+        let mut uv = self.pan + (pointer - rect.min) / rect.size() / self.zoom;
+        if self.show_tiling {
+            uv = egui::vec2((uv.x * 2.0).fract(), (uv.y * 2.0).fract());
+        }
+        let [width, height] = layer.maps.size;
+        let x = ((uv.x * width as f32) as usize).min(width - 1);
+        let y = ((uv.y * height as f32) as usize).min(height - 1);
+        Some(y * width + x)
+    }
+
+    fn default_export_path(&self) -> PathBuf {
+        PathBuf::from(format!("terrain_{}.png", self.layer().seed))
+    }
+
+    // Use the path typed by the user, or fall back to terrain_<seed>.png
+    fn export_target(&self) -> PathBuf {
+        let path = self.export_path.trim();
+        if path.is_empty() {
+            self.default_export_path()
+        } else {
+            PathBuf::from(path)
+        }
+    }
+
+    // Show the outcome of a file operation in the status line
+    fn report<E: fmt::Display>(&mut self, done: &str, path: &Path, result: Result<(), E>) {
+        self.status = Some(match result {
+            Ok(()) => format!("{} {}", done, path.display()),
+            Err(err) => format!("Error with {}: {}", path.display(), err),
+        });
+    }
+
+    fn save_config(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.layer().saved_config())?)
+    }
+
+    // Replace the config (and seed, if present) with the contents of a saved file; see `apply_saved`
+    fn load_config(&mut self, path: &Path) -> io::Result<Vec<String>> {
+        let saved = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(self.layer_mut().apply_saved(saved))
     }
 
-    fn quantize_color(color: [u8; 3], pixel_size: u32) -> Color32 {
-        let quantize = |v: u8| {
-            let step = 255 / pixel_size;
-            ((v as f32 / step as f32).round() * step as f32) as u8
+    // Report a load, adding what had to change to bring it within the limits
+    fn report_loaded<E: fmt::Display>(&mut self, done: &str, path: &Path, result: Result<Vec<String>, E>) {
+        let notes = result.as_ref().map_or_else(|_| Vec::new(), Clone::clone);
+        self.report(done, path, result.map(drop));
+        if notes.is_empty() {
+            return;
+        }
+        if let Some(status) = &mut self.status {
+            status.push_str(&format!(", adjusted: {}", notes.join("; ")));
+        }
+    }
+
+    // Use <name>_<suffix>.png next to the color export
+    fn suffixed_target(&self, suffix: &str) -> PathBuf {
+        let path = self.export_target();
+        let stem = path.file_stem().map_or_else(|| "terrain".into(), |stem| stem.to_string_lossy());
+        path.with_file_name(format!("{}_{}.png", stem, suffix))
+    }
+
+    // <name>_tiles next to the color export
+    fn tiles_dir(&self) -> PathBuf {
+        let path = self.export_target();
+        let stem = path.file_stem().map_or_else(|| "terrain".into(), |stem| stem.to_string_lossy());
+        path.with_file_name(format!("{}_tiles", stem))
+    }
+
+    fn export_tiles(&self, dir: &Path) -> image::ImageResult<usize> {
+        fs::create_dir_all(dir)?;
+        save_tiles(&self.layer().view_image(), self.tile_size as usize, dir)
+    }
+
+    fn export_heightmap(&self, path: &Path) -> image::ImageResult<()> {
+        let layer = self.layer();
+        layer.check_maps()?;
+        save_heightmap(&layer.maps, path)
+    }
+
+    // The mesh, its .mtl next to it and the color image as <name>_texture.png for the material to use
+    fn export_obj(&self, path: &Path) -> image::ImageResult<()> {
+        let layer = self.layer();
+        layer.check_maps()?;
+        let texture = self.suffixed_target("texture");
+        save_png(&layer.terrain, None, &texture)?;
+        let texture_name = texture.file_name().map_or_else(|| "terrain_texture.png".into(), |name| name.to_string_lossy());
+        save_obj(&layer.maps, self.mesh_stride as usize, self.orbit.exaggeration as f64, &texture_name, path)?;
+        Ok(())
+    }
+
+    // Same resolution as the heightmap, so the two register pixel for pixel
+    fn export_normal_map(&self, path: &Path) -> image::ImageResult<()> {
+        let layer = self.layer();
+        layer.check_maps()?;
+        save_normal_map(&layer.maps, layer.config.normal_strength, path)
+    }
+
+    // Put the full-resolution view on the system clipboard as a bitmap
+    fn copy_to_clipboard(&mut self) -> Result<(), arboard::Error> {
+        let terrain = self.layers[self.active].view_image();
+        let [width, height] = terrain.size;
+        let bytes: Vec<u8> = terrain.pixels.iter().flat_map(|c| [c.r(), c.g(), c.b(), 255]).collect();
+        // On X11 the clipboard contents are dropped with the last handle, so keep it around
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self.clipboard.insert(arboard::Clipboard::new()?),
         };
+        clipboard.set_image(arboard::ImageData {
+            width,
+            height,
+            bytes: bytes.into(),
+        })
+    }
+
+    // Write the terrain at its generated resolution, ignoring the preview scale
+    // An imported image without parameters, or a map colored from imported heights, wasn't made by the current
+    // config and seed alone, so it is saved without them
+    fn export_png(&self, path: &Path) -> image::ImageResult<()> {
+        let layer = self.layer();
+        let parameters = (!layer.viewing_import && !layer.uses_heightmap()).then(|| SavedConfig::embedded(&layer.config, layer.seed));
+        save_png(&layer.view_image(), parameters.as_deref(), path)
+    }
+
+    // Import an exported PNG and report the outcome; true if parameters were restored
+    fn open_png(&mut self, ctx: &egui::Context, path: &Path) -> bool {
+        match self.import_png(ctx, path) {
+            Ok(Some(notes)) => {
+                self.report_loaded("Imported parameters from", path, Ok::<_, String>(notes));
+                true
+            }
+            Ok(None) => {
+                self.status = Some(format!("No parameters found in {}; showing the image only", path.display()));
+                false
+            }
+            Err(err) => {
+                self.report("Imported", path, Err(err));
+                false
+            }
+        }
+    }
+
+    // Load a file dropped onto the window by its extension; true if the terrain needs regenerating
+    fn open_dropped(&mut self, ctx: &egui::Context, path: &Path) -> bool {
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("json") => {
+                let result = self.load_config(path);
+                let loaded = result.is_ok();
+                self.report_loaded("Loaded", path, result);
+                loaded
+            }
+            Some("png") => self.open_png(ctx, path),
+            _ => {
+                self.status = Some(format!("Can't open {}: drop a .json config or an exported .png", path.display()));
+                false
+            }
+        }
+    }
+
+    // Restore the parameters embedded in an exported PNG; returns whether they were found. Without them
+    // the image is only shown, with no maps behind it, until the next generation replaces it.
+    // Notes on what had to change if parameters were restored (see `apply_saved`), None if there were none
+    fn import_png(&mut self, ctx: &egui::Context, path: &Path) -> Result<Option<Vec<String>>, String> {
+        let (image, parameters) = load_png(path).map_err(|err| err.to_string())?;
+        if let Some(parameters) = parameters {
+            let saved: SavedConfig = serde_json::from_str(&parameters).map_err(|err| err.to_string())?;
+            return Ok(Some(self.layer_mut().apply_saved(saved)));
+        }
+        self.layer_mut().show_image(ctx, image);
+        Ok(None)
+    }
+}
+
+// Make the font at `path` the first choice for proportional text and a fallback for monospace. The file is
+// checked first, since egui panics on data it can't parse.
+fn install_font(ctx: &egui::Context, path: &Path) -> io::Result<()> {
+    let font_data = fs::read(path)?;
+    ab_glyph::FontRef::try_from_slice(&font_data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut fonts = FontDefinitions::default();
+    fonts.font_data.insert("my_font".to_owned(), egui::FontData::from_owned(font_data));
+    fonts.families.entry(FontFamily::Proportional).or_default().insert(0, "my_font".to_owned());
+    fonts.families.entry(FontFamily::Monospace).or_default().push("my_font".to_owned());
+    ctx.set_fonts(fonts);
+    Ok(())
+}
+
+// The one source of nondeterminism: a fresh world seed for New Seed and the actions that pick a seed the
+// same way (Randomize, Reset, re-rolling the gallery, and startup or the CLI without one). Everything
+// else random, from erosion droplets to scatter points, is drawn from a StdRng seeded by the world seed.
+fn new_seed() -> u32 {
+    rand::thread_rng().gen()
+}
+
+// 32-bit FNV-1a, so a name maps to the same seed on every platform and Rust version
+fn seed_from_name(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193))
+}
+
+// Nearest-neighbour downscale so the longer side is at most `max_side`, keeping the aspect ratio
+fn thumbnail(image: &egui::ColorImage, max_side: usize) -> egui::ColorImage {
+    let [width, height] = image.size;
+    let factor = (max_side as f64 / width.max(height).max(1) as f64).min(1.0);
+    let size = [((width as f64 * factor) as usize).max(1), ((height as f64 * factor) as usize).max(1)];
+    let mut pixels = Vec::with_capacity(size[0] * size[1]);
+    for y in 0..size[1] {
+        let source_y = (y * height / size[1]).min(height.saturating_sub(1));
+        for x in 0..size[0] {
+            let source_x = (x * width / size[0]).min(width.saturating_sub(1));
+            pixels.push(image.pixels[source_y * width + source_x]);
+        }
+    }
+    egui::ColorImage { size, pixels }
+}
+
+// A slider that also steps with the scroll wheel while hovered: one notch moves a hundredth of the range,
+// or a thousandth with Shift (which egui turns into horizontal scrolling). Logarithmic sliders step by a
+// ratio instead and integer ones by at least one. The scroll is consumed so nothing behind it scrolls too,
+// with scroll areas checking `wheel_slider_hovered`. Once focused, by clicking or with Tab, the arrow keys
+// step it the same way a notch does: right and up increase it, left and down decrease it.
+fn wheel_slider<Num: egui::emath::Numeric>(ui: &mut egui::Ui, value: &mut Num, range: RangeInclusive<Num>, logarithmic: bool, text: &str) -> egui::Response {
+    let before = *value;
+    let mut slider = egui::Slider::new(&mut *value, range.clone()).logarithmic(logarithmic);
+    if !text.is_empty() {
+        slider = slider.text(text);
+    }
+    let mut response = ui.add(slider);
+    if !response.enabled() {
+        return response;
+    }
+    let fine = ui.input(|i| i.modifiers.shift);
+    let step = if fine { WHEEL_FINE_STEP } else { WHEEL_STEP };
+    if response.has_focus() {
+        let presses = ui.input(|i| {
+            let count = |key| i.num_presses(key) as f64;
+            count(egui::Key::ArrowRight) + count(egui::Key::ArrowUp) - count(egui::Key::ArrowLeft) - count(egui::Key::ArrowDown)
+        });
+        // The slider has already moved itself by one screen pixel per press, which depends on its width, so
+        // step from the value it had before instead
+        if presses != 0.0 {
+            *value = before;
+            if nudge(value, &range, logarithmic, presses * step) {
+                response.mark_changed();
+            }
+        }
+    }
+    if !response.hovered() {
+        return response;
+    }
+    let frame = ui.ctx().frame_nr();
+    ui.ctx().data_mut(|data| data.insert_temp(egui::Id::new(WHEEL_HOVER_ID), frame));
+    let scroll = ui.input(|i| i.scroll_delta.x + i.scroll_delta.y);
+    if scroll == 0.0 {
+        return response;
+    }
+    ui.input_mut(|i| i.scroll_delta = egui::Vec2::ZERO);
+    if nudge(value, &range, logarithmic, (scroll / WHEEL_NOTCH) as f64 * step) {
+        response.mark_changed();
+    }
+    response
+}
+
+// A wheel slider followed by a ↺ button that puts back `default`, enabled while the value differs from it.
+// A reset shows up as a change of the slider's response.
+fn reset_slider<Num: egui::emath::Numeric>(ui: &mut egui::Ui, value: &mut Num, default: Num, range: RangeInclusive<Num>, logarithmic: bool, text: &str) -> egui::Response {
+    ui.horizontal(|ui| {
+        let mut response = wheel_slider(ui, value, range, logarithmic, text);
+        let changed = value.to_f64() != default.to_f64();
+        if ui.add_enabled(changed, egui::Button::new("↺").small()).on_hover_text("Reset to the default").clicked() {
+            *value = default;
+            response.mark_changed();
+        }
+        response
+    })
+    .inner
+}
 
-        Color32::from_rgb(
-            quantize(color[0]),
-            quantize(color[1]),
-            quantize(color[2]),
-        )
+// Move a slider value by `fraction` of its range, or by that share of its ratio when logarithmic; integer
+// values always move by at least one. Returns whether the value changed.
+fn nudge<Num: egui::emath::Numeric>(value: &mut Num, range: &RangeInclusive<Num>, logarithmic: bool, fraction: f64) -> bool {
+    let (min, max, current) = (range.start().to_f64(), range.end().to_f64(), value.to_f64());
+    let mut next = if logarithmic && min > 0.0 { current * (max / min).powf(fraction) } else { current + (max - min) * fraction };
+    if Num::INTEGRAL {
+        next = if next.round() == current { current + fraction.signum() } else { next.round() };
     }
+    let next = Num::from_f64(next.clamp(min, max));
+    if next.to_f64() == current {
+        return false;
+    }
+    *value = next;
+    true
+}
+
+// Whether a wheel slider was hovered this frame or the last. Scroll areas read the wheel before their
+// contents are drawn, so they check this to leave it to the slider.
+fn wheel_slider_hovered(ctx: &egui::Context) -> bool {
+    let hovered = ctx.data(|data| data.get_temp::<u64>(egui::Id::new(WHEEL_HOVER_ID)));
+    hovered.is_some_and(|frame| frame + 1 >= ctx.frame_nr())
+}
+
+// Square plot of a curve over 0..1 with a draggable handle per point. The end points only move vertically
+// and interior points can't pass their neighbors, so the points stay sorted.
+fn curve_editor(ui: &mut egui::Ui, curve: &mut Curve) -> bool {
+    let mut changed = false;
+    let (rect, response) = ui.allocate_exact_size(egui::Vec2::splat(CURVE_EDITOR_SIZE), egui::Sense::click());
+    let to_screen = |(x, y): (f64, f64)| egui::pos2(rect.left() + x as f32 * rect.width(), rect.bottom() - y as f32 * rect.height());
+    let from_screen = |pos: egui::Pos2| {
+        let x = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64;
+        let y = ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0) as f64;
+        (x, y)
+    };
+
+    if response.double_clicked() {
+        if let Some((x, _)) = response.interact_pointer_pos().map(from_screen) {
+            let at = curve.points.iter().position(|point| point.0 > x).unwrap_or(curve.points.len());
+            if at > 0 && at < curve.points.len() {
+                curve.points.insert(at, (x, curve.evaluate(x)));
+                changed = true;
+            }
+        }
+    }
+
+    let last = curve.points.len().saturating_sub(1);
+    let mut remove = None;
+    for index in 0..curve.points.len() {
+        let handle = egui::Rect::from_center_size(to_screen(curve.points[index]), egui::Vec2::splat(CURVE_HANDLE_SIZE));
+        let handle_response = ui.interact(handle, response.id.with(index), egui::Sense::click_and_drag());
+        if handle_response.dragged() {
+            if let Some((x, y)) = handle_response.interact_pointer_pos().map(from_screen) {
+                let x = if index == 0 || index == last {
+                    curve.points[index].0
+                } else {
+                    x.clamp(curve.points[index - 1].0, curve.points[index + 1].0)
+                };
+                curve.points[index] = (x, y);
+                changed = true;
+            }
+        }
+        if handle_response.secondary_clicked() && index != 0 && index != last {
+            remove = Some(index);
+        }
+    }
+    if let Some(index) = remove {
+        curve.points.remove(index);
+        changed = true;
+    }
+
+    let painter = ui.painter_at(rect.expand(CURVE_HANDLE_SIZE));
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+    painter.line_segment([rect.left_bottom(), rect.right_top()], egui::Stroke::new(1.0, visuals.weak_text_color()));
+    let line: Vec<egui::Pos2> = curve.points.iter().map(|&point| to_screen(point)).collect();
+    painter.add(egui::Shape::line(line.clone(), egui::Stroke::new(2.0, visuals.strong_text_color())));
+    for point in line {
+        painter.circle_filled(point, CURVE_HANDLE_SIZE / 2.0, visuals.selection.bg_fill);
+    }
+    changed
+}
+
+// A preview strip with a draggable handle under each stop, then one row per stop for exact values.
+// Double-clicking the strip adds a stop there.
+fn gradient_editor(ui: &mut egui::Ui, gradient: &mut Gradient) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_label("Interpolation")
+        .selected_text(gradient.interpolation.label())
+        .show_ui(ui, |ui| {
+            for interpolation in Interpolation::ALL {
+                changed |= ui.selectable_value(&mut gradient.interpolation, interpolation, interpolation.label()).changed();
+            }
+        });
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), GRADIENT_STRIP_HEIGHT + GRADIENT_HANDLE_SIZE), egui::Sense::click());
+    let strip = egui::Rect::from_min_size(rect.min, egui::vec2(rect.width(), GRADIENT_STRIP_HEIGHT));
+    let position_at = |x: f32| ((x - strip.left()) / strip.width()).clamp(0.0, 1.0) as f64;
+    let columns = strip.width().max(1.0) as usize;
+    for column in 0..columns {
+        let x = strip.left() + column as f32;
+        let color = gradient.sample(position_at(x + 0.5));
+        ui.painter().rect_filled(egui::Rect::from_min_max(egui::pos2(x, strip.top()), egui::pos2(x + 1.0, strip.bottom())), 0.0, color);
+    }
+    if response.double_clicked() {
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let position = position_at(pointer.x);
+            gradient.stops.push((position, gradient.sample(position)));
+            changed = true;
+        }
+    }
+
+    for (index, stop) in gradient.stops.iter_mut().enumerate() {
+        let x = strip.left() + stop.0 as f32 * strip.width();
+        let tip = egui::pos2(x, strip.bottom());
+        let handle = egui::Rect::from_center_size(tip + egui::vec2(0.0, GRADIENT_HANDLE_SIZE / 2.0), egui::Vec2::splat(GRADIENT_HANDLE_SIZE));
+        let handle_response = ui.interact(handle, response.id.with(index), egui::Sense::drag());
+        if handle_response.dragged() {
+            if let Some(pointer) = handle_response.interact_pointer_pos() {
+                stop.0 = position_at(pointer.x);
+                changed = true;
+            }
+        }
+        let outline = ui.visuals().widgets.style(&handle_response).fg_stroke;
+        let points = vec![tip, handle.left_bottom(), handle.right_bottom()];
+        ui.painter().add(egui::Shape::convex_polygon(points, stop.1, outline));
+    }
+
+    let mut remove = None;
+    let removable = gradient.stops.len() > 1;
+    for (index, stop) in gradient.stops.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= wheel_slider(ui, &mut stop.0, 0.0..=1.0, false, "").changed();
+            changed |= ui.color_edit_button_srgba(&mut stop.1).changed();
+            if ui.add_enabled(removable, egui::Button::new("Remove").small()).clicked() {
+                remove = Some(index);
+            }
+        });
+    }
+    if let Some(index) = remove {
+        gradient.stops.remove(index);
+        changed = true;
+    }
+    if ui.button("Add Stop").clicked() {
+        gradient.stops.push((0.5, gradient.sample(0.5)));
+        changed = true;
+    }
+    // Reordering mid-drag would hand the drag to a different stop, so only sort once it's released
+    if !ui.memory(|memory| memory.is_anything_being_dragged()) {
+        gradient.sort();
+    }
+    changed
 }
 
 fn main() -> eframe::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_headless(&args) {
+        match cli::run(&args) {
+            Ok(path) => {
+                println!("Saved {}", path.display());
+                return Ok(());
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let options = eframe::NativeOptions {
-        initial_window_size: Some(egui::vec2(530.0, 680.0)),
+        initial_window_size: Some(egui::vec2(1100.0, 760.0)),
         ..Default::default()
     };
     eframe::run_native(
@@ -189,3 +1455,27 @@ fn main() -> eframe::Result<()> {
         Box::new(|cc| Box::new(TerrainApp::new(cc))),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loaded_configs_are_limited() {
+        let mut saved = SavedConfig {
+            seed: Some(1),
+            config: TerrainConfig { width: 100_000, height: 10, supersample: 4, ..TerrainConfig::default() },
+        };
+        assert_eq!(saved.validate().len(), 3);
+        assert_eq!([saved.config.width, saved.config.height], [MAX_DIMENSION, MIN_DIMENSION]);
+        assert_eq!(saved.config.supersample, 2);
+
+        let mut flat = SavedConfig { seed: None, config: TerrainConfig { continent_scale: 0.0, world_span: -1.0, ..TerrainConfig::default() } };
+        assert_eq!(flat.config.useful_octaves(), usize::MAX);
+        assert_eq!(flat.validate().len(), 2);
+        assert_eq!([flat.config.continent_scale, flat.config.world_span], [*CONTINENT_SCALE_RANGE.start(), *WORLD_SPAN_RANGE.start()]);
+
+        let mut fine = SavedConfig { seed: None, config: TerrainConfig::default() };
+        assert!(fine.validate().is_empty());
+    }
+}
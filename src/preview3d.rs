@@ -0,0 +1,116 @@
+// Software-projected 3D view of the height buffer, drawn as a colored mesh with egui's painter
+
+use crate::terrain::TerrainMaps;
+use crate::wheel_slider;
+use eframe::egui;
+use egui::Color32;
+
+// Grid cells along the longer side of the map; the mesh is rebuilt every frame, so it stays coarse
+const MESH_RESOLUTION: usize = 128;
+
+// Fraction of the view the map's longer side spans at zoom 1
+const FIT: f32 = 0.7;
+
+// Radians of orbit per point dragged, and pitch limits (horizontal view to straight down)
+const ORBIT_SPEED: f32 = 0.01;
+const MIN_PITCH: f32 = 0.1;
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2;
+
+// Height of the full 0..1 range relative to the map's longer side at exaggeration 1
+const VERTICAL_SCALE: f32 = 0.2;
+
+pub struct Orbit {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub exaggeration: f32,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Self {
+            yaw: std::f32::consts::FRAC_PI_4,
+            pitch: 0.6,
+            exaggeration: 1.0,
+        }
+    }
+}
+
+impl Orbit {
+    // Screen offset from the view center and depth (larger is farther) of a point in world units
+    fn project(&self, [x, y, z]: [f32; 3]) -> (egui::Vec2, f32) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let (across, along) = (x * cos_yaw - y * sin_yaw, x * sin_yaw + y * cos_yaw);
+        let screen = egui::vec2(across, along * sin_pitch - z * cos_pitch);
+        (screen, along * cos_pitch - z * sin_pitch)
+    }
+}
+
+// Draw the terrain as a mesh colored from `image`, using the painter's algorithm: cells are sorted far to
+// near so closer ones cover the ones behind. Dragging orbits the camera.
+pub fn show(ui: &mut egui::Ui, orbit: &mut Orbit, maps: &TerrainMaps, image: &egui::ColorImage) {
+    wheel_slider(ui, &mut orbit.exaggeration, 0.1..=5.0, false, "Vertical Exaggeration");
+    let size = ui.available_size().max(egui::vec2(200.0, 150.0));
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+    let delta = response.drag_delta();
+    orbit.yaw += delta.x * ORBIT_SPEED;
+    orbit.pitch = (orbit.pitch + delta.y * ORBIT_SPEED).clamp(MIN_PITCH, MAX_PITCH);
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+    let [width, height] = maps.size;
+    if width < 2 || height < 2 || image.size != maps.size {
+        return;
+    }
+
+    let longest = width.max(height);
+    let step = longest.div_ceil(MESH_RESOLUTION).max(1);
+    let (columns, rows) = ((width - 1) / step + 1, (height - 1) / step + 1);
+    let scale = rect.width().min(rect.height()) * FIT;
+    let vertical = VERTICAL_SCALE * orbit.exaggeration;
+    let light = [-0.5f64, -0.5, 0.7];
+
+    // Project every grid vertex once
+    let mut points = Vec::with_capacity(columns * rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = ((column * step).min(width - 1), (row * step).min(height - 1));
+            let world = [
+                x as f32 / longest as f32 - 0.5 * width as f32 / longest as f32,
+                y as f32 / longest as f32 - 0.5 * height as f32 / longest as f32,
+                (maps.heights[y * width + x] as f32 - 0.5) * vertical,
+            ];
+            let (offset, depth) = orbit.project(world);
+            points.push((rect.center() + offset * scale, depth));
+        }
+    }
+
+    let mut cells: Vec<(f32, usize, usize)> = Vec::with_capacity((columns - 1) * (rows - 1));
+    for row in 0..rows - 1 {
+        for column in 0..columns - 1 {
+            let corner = |dx: usize, dy: usize| points[(row + dy) * columns + column + dx].1;
+            let depth = (corner(0, 0) + corner(1, 0) + corner(0, 1) + corner(1, 1)) / 4.0;
+            cells.push((depth, column, row));
+        }
+    }
+    cells.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut mesh = egui::Mesh::default();
+    for (_, column, row) in cells {
+        let (x, y) = (column * step, row * step);
+        // Light each cell from the upper left so relief reads even without hillshading
+        let normal = maps.normal(x, y, orbit.exaggeration as f64);
+        let lit = (normal[0] * light[0] + normal[1] * light[1] + normal[2] * light[2]).max(0.0) as f32;
+        let base = image.pixels[y * width + x];
+        let shade = |channel: u8| (channel as f32 * (0.5 + 0.6 * lit)).min(255.0) as u8;
+        let color = Color32::from_rgb(shade(base.r()), shade(base.g()), shade(base.b()));
+
+        let first = mesh.vertices.len() as u32;
+        for (dx, dy) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+            mesh.colored_vertex(points[(row + dy) * columns + column + dx].0, color);
+        }
+        mesh.add_triangle(first, first + 1, first + 2);
+        mesh.add_triangle(first, first + 2, first + 3);
+    }
+    painter.add(egui::Shape::mesh(mesh));
+}
@@ -0,0 +1,105 @@
+// Voronoi partition of the map into regions, such as nations, independent of the elevation
+
+use eframe::egui;
+use egui::ecolor::Hsva;
+use egui::Color32;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+// Hue step between consecutive regions, the golden ratio's fractional part, so neighbouring indices
+// never get similar colors
+const HUE_STEP: f32 = 0.618_034;
+
+// Sites are scattered uniformly over the map and bucketed into a square grid of about one site per cell,
+// so a nearest-site lookup only has to check the cells around a pixel
+pub struct Regions {
+    // Site positions in world units, the map's longer side being 1
+    sites: Vec<[f64; 2]>,
+    // Site indices per grid cell, row-major
+    cells: Vec<Vec<usize>>,
+    columns: usize,
+    rows: usize,
+    extent: [f64; 2],
+}
+
+impl Regions {
+    // `count` sites for a map of `size` pixels, placed from `seed`. Positions are relative to the map, so
+    // the same seed puts the regions in the same places at any resolution.
+    pub fn new(count: usize, size: [usize; 2], seed: u32) -> Self {
+        let longest = size[0].max(size[1]).max(1) as f64;
+        let extent = [size[0] as f64 / longest, size[1] as f64 / longest];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let sites: Vec<[f64; 2]> = (0..count.max(1)).map(|_| [rng.gen::<f64>() * extent[0], rng.gen::<f64>() * extent[1]]).collect();
+
+        let side = (sites.len() as f64).sqrt().ceil() as usize;
+        let (columns, rows) = (side, side);
+        let mut cells = vec![Vec::new(); columns * rows];
+        for (index, site) in sites.iter().enumerate() {
+            let (column, row) = Self::cell_of(site, extent, columns, rows);
+            cells[row * columns + column].push(index);
+        }
+        Self { sites, cells, columns, rows, extent }
+    }
+
+    fn cell_of(point: &[f64; 2], extent: [f64; 2], columns: usize, rows: usize) -> (usize, usize) {
+        let column = ((point[0] / extent[0].max(f64::EPSILON) * columns as f64) as usize).min(columns - 1);
+        let row = ((point[1] / extent[1].max(f64::EPSILON) * rows as f64) as usize).min(rows - 1);
+        (column, row)
+    }
+
+    // Index of the site closest to a point in world units. Rings of cells are searched outward from the
+    // point's own cell; once the closest site so far is nearer than anything a further ring could hold,
+    // the search stops.
+    pub fn nearest(&self, point: [f64; 2]) -> usize {
+        let (column, row) = Self::cell_of(&point, self.extent, self.columns, self.rows);
+        let cell_size = (self.extent[0] / self.columns as f64).min(self.extent[1] / self.rows as f64);
+        let (mut best, mut best_distance) = (0, f64::INFINITY);
+        for ring in 0..=self.columns.max(self.rows) {
+            let (left, right) = (column.saturating_sub(ring), (column + ring).min(self.columns - 1));
+            let (top, bottom) = (row.saturating_sub(ring), (row + ring).min(self.rows - 1));
+            for y in top..=bottom {
+                for x in left..=right {
+                    // Only the cells on the ring's border; the inside was searched already
+                    if x.abs_diff(column) != ring && y.abs_diff(row) != ring {
+                        continue;
+                    }
+                    for &index in &self.cells[y * self.columns + x] {
+                        let [sx, sy] = self.sites[index];
+                        let distance = (sx - point[0]).powi(2) + (sy - point[1]).powi(2);
+                        if distance < best_distance {
+                            (best, best_distance) = (index, distance);
+                        }
+                    }
+                }
+            }
+            // Sites in the next ring are at least `ring` whole cells away
+            let reach = ring as f64 * cell_size;
+            if best_distance <= reach * reach {
+                break;
+            }
+        }
+        best
+    }
+
+    // The region of every pixel of a map of `size`, row-major. Each `block`×`block` block takes the
+    // region at its top-left pixel, so blocky maps keep solid blocks.
+    pub fn assign(&self, size: [usize; 2], block: usize) -> Vec<usize> {
+        let [width, height] = size;
+        let longest = width.max(height).max(1) as f64;
+        let block = block.max(1);
+        (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let (x, y) = (index % width / block * block, index / width / block * block);
+                self.nearest([(x as f64 + 0.5) / longest, (y as f64 + 0.5) / longest])
+            })
+            .collect()
+    }
+}
+
+// A distinct, fairly saturated color per region, spreading the hues evenly around the wheel
+pub fn region_color(index: usize) -> Color32 {
+    let hue = (index as f32 * HUE_STEP).fract();
+    let value = if index.is_multiple_of(2) { 0.9 } else { 0.75 };
+    Color32::from(Hsva::new(hue, 0.55, value, 1.0))
+}
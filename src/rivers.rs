@@ -0,0 +1,103 @@
+// Drainage analysis for drawing rivers on a height buffer
+
+use crate::erosion::NEIGHBORS;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// Raise applied per cell when filling pits and flats, so every filled cell still drains somewhere
+const FILL_EPSILON: f64 = 1e-7;
+
+// Cells filled by more than this are inside a pit rather than on a flat
+const LAKE_DEPTH: f64 = 1e-4;
+
+// Min-heap entry for the priority flood
+struct Pending {
+    height: f64,
+    index: usize,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.height.total_cmp(&self.height).then(other.index.cmp(&self.index))
+    }
+}
+
+// Fraction of the map that drains through each cell. Pits and flats are first filled by a priority flood
+// from the map edge, so all water eventually leaves the map instead of pooling; each cell then sends its
+// water to the neighbor with the steepest descent on the filled surface. Filled pits still pass their
+// water on but report no flow themselves, since the route across them is an arbitrary straight line.
+pub fn flow_accumulation(heights: &[f64], size: [usize; 2]) -> Vec<f64> {
+    let [width, height] = size;
+    let count = heights.len();
+    if count == 0 {
+        return Vec::new();
+    }
+    let neighbor = |index: usize, dx: isize, dy: isize| {
+        let (x, y) = (index % width, index / width);
+        let (nx, ny) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        (nx < width && ny < height).then_some(ny * width + nx)
+    };
+
+    // Cells come off the heap in non-decreasing filled height, which is also a valid drainage order
+    let mut filled = heights.to_vec();
+    let mut visited = vec![false; count];
+    let mut heap = BinaryHeap::new();
+    for index in 0..count {
+        let (x, y) = (index % width, index / width);
+        if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+            visited[index] = true;
+            heap.push(Pending { height: filled[index], index });
+        }
+    }
+    let mut order = Vec::with_capacity(count);
+    while let Some(Pending { index, .. }) = heap.pop() {
+        order.push(index);
+        for (dx, dy, _) in NEIGHBORS {
+            if let Some(next) = neighbor(index, dx, dy) {
+                if !visited[next] {
+                    visited[next] = true;
+                    filled[next] = filled[next].max(filled[index] + FILL_EPSILON);
+                    heap.push(Pending { height: filled[next], index: next });
+                }
+            }
+        }
+    }
+
+    // Accumulate from the highest cells down, so each cell is complete before it passes its water on
+    let mut flow = vec![1.0; count];
+    for &index in order.iter().rev() {
+        let mut receiver = None;
+        let mut steepest = 0.0;
+        for (dx, dy, distance) in NEIGHBORS {
+            if let Some(next) = neighbor(index, dx, dy) {
+                let slope = (filled[index] - filled[next]) / distance;
+                if slope > steepest {
+                    steepest = slope;
+                    receiver = Some(next);
+                }
+            }
+        }
+        if let Some(receiver) = receiver {
+            flow[receiver] += flow[index];
+        }
+    }
+    let total = count as f64;
+    for (index, cells) in flow.iter_mut().enumerate() {
+        *cells = if filled[index] - heights[index] > LAKE_DEPTH { 0.0 } else { *cells / total };
+    }
+    flow
+}
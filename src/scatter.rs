@@ -0,0 +1,73 @@
+// Evenly spaced decoration points, such as trees or towns, on one biome of a generated map
+
+use crate::terrain::{TerrainConfig, TerrainMaps};
+use rand::{Rng, SeedableRng};
+use std::f64::consts::TAU;
+
+// Added to the world seed, like the noise field offsets in terrain.rs, so the points don't follow the
+// droplet erosion's random sequence
+const SCATTER_SEED_OFFSET: u32 = 4;
+
+// Candidates tried around each active point before it is retired, as in Bridson's paper
+const ATTEMPTS: usize = 30;
+
+// Poisson-disk sample the whole map with Bridson's algorithm so that no two points are closer than
+// `spacing` pixels, then keep those whose pixel has the biome called `biome`. Sampling everywhere first
+// keeps the spacing even right up to a biome's edges and reaches every patch of it, however small.
+pub fn scatter(config: &TerrainConfig, maps: &TerrainMaps, biome: &str, spacing: f64) -> Vec<[usize; 2]> {
+    let [width, height] = maps.size;
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let spacing = spacing.max(1.0);
+    let cell = spacing / std::f64::consts::SQRT_2;
+    let (columns, rows) = ((width as f64 / cell).ceil() as usize, (height as f64 / cell).ceil() as usize);
+    let cell_of = |[x, y]: [f64; 2]| ((x / cell) as usize).min(columns - 1) + ((y / cell) as usize).min(rows - 1) * columns;
+    // Each cell is small enough to hold at most one point
+    let mut grid: Vec<Option<usize>> = vec![None; columns * rows];
+    let mut points: Vec<[f64; 2]> = Vec::new();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(maps.seed.wrapping_add(SCATTER_SEED_OFFSET) as u64);
+
+    let first = [rng.gen::<f64>() * width as f64, rng.gen::<f64>() * height as f64];
+    grid[cell_of(first)] = Some(0);
+    points.push(first);
+    let mut active = vec![0];
+    while !active.is_empty() {
+        let slot = rng.gen_range(0..active.len());
+        let [x, y] = points[active[slot]];
+        let mut placed = false;
+        for _ in 0..ATTEMPTS {
+            // Uniform over the ring from one to two spacings away
+            let angle = rng.gen::<f64>() * TAU;
+            let distance = spacing * (1.0 + 3.0 * rng.gen::<f64>()).sqrt();
+            let candidate = [x + distance * angle.cos(), y + distance * angle.sin()];
+            if candidate[0] < 0.0 || candidate[1] < 0.0 || candidate[0] >= width as f64 || candidate[1] >= height as f64 {
+                continue;
+            }
+            let (column, row) = ((candidate[0] / cell) as usize, (candidate[1] / cell) as usize);
+            let near = (row.saturating_sub(2)..=(row + 2).min(rows - 1))
+                .flat_map(|ny| (column.saturating_sub(2)..=(column + 2).min(columns - 1)).map(move |nx| ny * columns + nx))
+                .filter_map(|index| grid[index])
+                .any(|other| {
+                    let [ox, oy] = points[other];
+                    (ox - candidate[0]).powi(2) + (oy - candidate[1]).powi(2) < spacing * spacing
+                });
+            if !near {
+                grid[cell_of(candidate)] = Some(points.len());
+                active.push(points.len());
+                points.push(candidate);
+                placed = true;
+                break;
+            }
+        }
+        if !placed {
+            active.swap_remove(slot);
+        }
+    }
+
+    points
+        .into_iter()
+        .map(|[x, y]| [x as usize, y as usize])
+        .filter(|&[x, y]| config.pixel_biome(maps, y * width + x).is_some_and(|found| found.name == biome))
+        .collect()
+}
@@ -0,0 +1,68 @@
+// Picks a player spawn point from the classified biomes of a generated map
+
+use crate::terrain::{TerrainConfig, TerrainMaps};
+use rayon::prelude::*;
+
+// Biomes whose name contains this are preferred over any other land
+const PREFERRED_BIOME: &str = "grass";
+
+// A connected run of pixels sharing one land biome
+struct Region {
+    pixels: Vec<usize>,
+    preferred: bool,
+}
+
+// The pixel closest to the centroid of the largest connected grass region above the sea level, falling
+// back to the largest region of any land biome. Regions are 4-connected. The centroid of a curved
+// region can fall outside it, so the pin snaps to the nearest pixel that is inside.
+pub fn find_spawn(config: &TerrainConfig, maps: &TerrainMaps) -> Option<[usize; 2]> {
+    let [width, height] = maps.size;
+    let biomes: Vec<Option<&str>> = maps
+        .heights
+        .par_iter()
+        .enumerate()
+        .map(|(index, &h)| if config.underwater(h) { None } else { config.pixel_biome(maps, index).map(|biome| biome.name) })
+        .collect();
+
+    let mut visited = vec![false; biomes.len()];
+    let mut best: Option<Region> = None;
+    for start in 0..biomes.len() {
+        let Some(name) = biomes[start] else {
+            continue;
+        };
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut region = Region { pixels: Vec::new(), preferred: name.to_lowercase().contains(PREFERRED_BIOME) };
+        let mut stack = vec![start];
+        while let Some(index) = stack.pop() {
+            region.pixels.push(index);
+            let (x, y) = (index % width, index / width);
+            let neighbors = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            for next in neighbors.into_iter().flatten() {
+                if !visited[next] && biomes[next] == Some(name) {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        let better = best.as_ref().is_none_or(|best| (region.preferred, region.pixels.len()) > (best.preferred, best.pixels.len()));
+        if better {
+            best = Some(region);
+        }
+    }
+
+    let pixels = best?.pixels;
+    let count = pixels.len() as f64;
+    let (sum_x, sum_y) = pixels.iter().fold((0.0, 0.0), |(sx, sy), &index| (sx + (index % width) as f64, sy + (index / width) as f64));
+    let centroid = (sum_x / count, sum_y / count);
+    let distance = |index: usize| ((index % width) as f64 - centroid.0).powi(2) + ((index / width) as f64 - centroid.1).powi(2);
+    let nearest = pixels.into_iter().min_by(|&a, &b| distance(a).total_cmp(&distance(b)))?;
+    Some([nearest % width, nearest / width])
+}
@@ -0,0 +1,1908 @@
+// Terrain generation and coloring, shared by the GUI and the headless CLI
+
+use crate::erosion;
+use crate::regions::{region_color, Regions};
+use crate::rivers;
+use eframe::egui;
+use egui::ecolor::Hsva;
+use egui::Color32;
+use noise::core::worley::{distance_functions, worley_2d, worley_3d, worley_4d, ReturnType};
+use noise::permutationtable::{NoiseHasher, PermutationTable};
+use noise::{NoiseFn, OpenSimplex, Perlin, Simplex};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// Height of the full 0..1 elevation range relative to the map width, used for slopes and normals
+const RELIEF: f64 = 0.2;
+
+// Every noise field's seed is derived from the world seed, so one seed reproduces the whole map. The
+// landmass field uses the world seed itself and both domain-warp fields read that same noise at the
+// base scale, the second one shifted by WARP_OFFSET. The others add a fixed offset (wrapping), and
+// droplet erosion seeds its StdRng with the world seed. Changing any of these changes every saved world.
+const MOISTURE_SEED_OFFSET: u32 = 1;
+const DETAIL_SEED_OFFSET: u32 = 2;
+const REGION_SEED_OFFSET: u32 = 3;
+
+// Where the second domain-warp field is read, so it is independent of the first
+const WARP_OFFSET: [f64; 2] = [5.2, 1.3];
+
+// Higher values keep more of the interior untouched by the island falloff
+const FALLOFF_POWER: f64 = 3.0;
+
+// Largest brightness change the color texture applies, as a factor either way
+const TEXTURE_RANGE: f64 = 0.25;
+
+// Droplet batches between the intermediate results erosion shows while it runs
+const EROSION_PREVIEW_STEPS: u32 = 5;
+
+// How often a paused job checks whether it was resumed or made stale
+const PAUSE_POLL: Duration = Duration::from_millis(20);
+
+// Heights this far either side of the sea level are pushed away from it by the coastline sharpness
+const COAST_BAND: f64 = 0.1;
+
+// How far below the plateau level heights start being drawn up to it
+const PLATEAU_BAND: f64 = 0.1;
+
+// Noise functions available from the noise crate
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum NoiseKind {
+    Perlin,
+    OpenSimplex,
+    Simplex,
+    Worley,
+}
+
+impl NoiseKind {
+    pub const ALL: [NoiseKind; 4] = [NoiseKind::Perlin, NoiseKind::OpenSimplex, NoiseKind::Simplex, NoiseKind::Worley];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NoiseKind::Perlin => "Perlin",
+            NoiseKind::OpenSimplex => "OpenSimplex",
+            NoiseKind::Simplex => "Simplex",
+            NoiseKind::Worley => "Worley",
+        }
+    }
+
+    fn build<const D: usize>(self, seed: u32, worley: WorleyOptions) -> Box<dyn NoiseFn<f64, D> + Send + Sync>
+    where
+        Lattice: NoiseFn<f64, D>,
+        OpenSimplex: NoiseFn<f64, D>,
+        Simplex: NoiseFn<f64, D>,
+        Cells: NoiseFn<f64, D>,
+    {
+        match self {
+            NoiseKind::Perlin => Box::new(Lattice { perlin: Perlin::new(seed), hasher: PermutationTable::new(seed) }),
+            NoiseKind::OpenSimplex => Box::new(OpenSimplex::new(seed)),
+            NoiseKind::Simplex => Box::new(Simplex::new(seed)),
+            NoiseKind::Worley => Box::new(Cells { hasher: PermutationTable::new(seed), options: worley }),
+        }
+    }
+}
+
+// Settings that only apply to Worley noise
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct WorleyOptions {
+    pub distance: WorleyDistance,
+    // Color each cell by its own random value instead of by the distance to its center
+    pub cell_values: bool,
+}
+
+impl Default for WorleyOptions {
+    fn default() -> Self {
+        Self {
+            distance: WorleyDistance::Euclidean,
+            cell_values: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WorleyDistance {
+    Euclidean,
+    EuclideanSquared,
+    Manhattan,
+    Chebyshev,
+}
+
+impl WorleyDistance {
+    pub const ALL: [WorleyDistance; 4] = [
+        WorleyDistance::Euclidean,
+        WorleyDistance::EuclideanSquared,
+        WorleyDistance::Manhattan,
+        WorleyDistance::Chebyshev,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WorleyDistance::Euclidean => "Euclidean",
+            WorleyDistance::EuclideanSquared => "Euclidean Squared",
+            WorleyDistance::Manhattan => "Manhattan",
+            WorleyDistance::Chebyshev => "Chebyshev",
+        }
+    }
+
+    fn function(self) -> fn(&[f64], &[f64]) -> f64 {
+        match self {
+            WorleyDistance::Euclidean => distance_functions::euclidean,
+            WorleyDistance::EuclideanSquared => distance_functions::euclidean_squared,
+            WorleyDistance::Manhattan => distance_functions::manhattan,
+            WorleyDistance::Chebyshev => distance_functions::chebyshev,
+        }
+    }
+
+    // Roughly the largest distance to the nearest cell center, measured by sampling
+    fn typical_max(self) -> f64 {
+        match self {
+            WorleyDistance::Euclidean => 0.9,
+            WorleyDistance::EuclideanSquared => 0.85,
+            WorleyDistance::Manhattan => 1.3,
+            WorleyDistance::Chebyshev => 0.9,
+        }
+    }
+}
+
+// Worley noise that can be shared between threads; noise::Worley keeps its distance function in an Rc
+struct Cells {
+    hasher: PermutationTable,
+    options: WorleyOptions,
+}
+
+impl Cells {
+    fn return_type(&self) -> ReturnType {
+        if self.options.cell_values {
+            ReturnType::Value
+        } else {
+            ReturnType::Distance
+        }
+    }
+
+    // Cell values already span -1..1, but distances depend on the distance function, so stretch them to
+    // the same range the other noise kinds use
+    fn remap(&self, value: f64) -> f64 {
+        if self.options.cell_values {
+            return value;
+        }
+        let distance = (value + 1.0) / 2.0 / self.options.distance.typical_max();
+        (distance * 2.0 - 1.0).clamp(-1.0, 1.0)
+    }
+}
+
+impl NoiseFn<f64, 2> for Cells {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        self.remap(worley_2d(&self.hasher, self.options.distance.function(), self.return_type(), point))
+    }
+}
+
+impl NoiseFn<f64, 3> for Cells {
+    fn get(&self, point: [f64; 3]) -> f64 {
+        self.remap(worley_3d(&self.hasher, self.options.distance.function(), self.return_type(), point))
+    }
+}
+
+impl NoiseFn<f64, 4> for Cells {
+    fn get(&self, point: [f64; 4]) -> f64 {
+        self.remap(worley_4d(&self.hasher, self.options.distance.function(), self.return_type(), point))
+    }
+}
+
+// Brings the 4D Perlin values to about the same spread as 2D Perlin, so tileable maps keep their look
+const LATTICE_4D_SCALE: f64 = 1.5;
+
+// Perlin noise with a 4D version that is continuous across lattice cells. noise::Perlin's 4D blend
+// jumps at every integer coordinate, which showed as seams all over tileable maps; 2D and 3D are
+// passed through unchanged.
+struct Lattice {
+    perlin: Perlin,
+    hasher: PermutationTable,
+}
+
+impl NoiseFn<f64, 2> for Lattice {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        self.perlin.get(point)
+    }
+}
+
+impl NoiseFn<f64, 3> for Lattice {
+    fn get(&self, point: [f64; 3]) -> f64 {
+        self.perlin.get(point)
+    }
+}
+
+impl NoiseFn<f64, 4> for Lattice {
+    // Blend the 16 corner gradients of the point's cell with quintic fades on each axis
+    fn get(&self, point: [f64; 4]) -> f64 {
+        let floored = point.map(f64::floor);
+        let corner = floored.map(|value| value as isize);
+        let distance: [f64; 4] = std::array::from_fn(|axis| point[axis] - floored[axis]);
+        let fade = distance.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+        let mut total = 0.0;
+        for bits in 0..16 {
+            let offset: [isize; 4] = std::array::from_fn(|axis| (bits >> axis) & 1);
+            let weight: f64 = (0..4).map(|axis| if offset[axis] == 1 { fade[axis] } else { 1.0 - fade[axis] }).product();
+            let hash = self.hasher.hash(&std::array::from_fn::<isize, 4, _>(|axis| corner[axis] + offset[axis]));
+            let relative: [f64; 4] = std::array::from_fn(|axis| distance[axis] - offset[axis] as f64);
+            total += weight * gradient_dot_4d(hash, relative);
+        }
+        (total * LATTICE_4D_SCALE).clamp(-1.0, 1.0)
+    }
+}
+
+// Dot product with one of the 32 gradients that have a zero on one axis and ±1 on the other three
+fn gradient_dot_4d(hash: usize, relative: [f64; 4]) -> f64 {
+    let zero = (hash >> 3) & 3;
+    let mut sign = 0;
+    let mut dot = 0.0;
+    for (axis, value) in relative.into_iter().enumerate() {
+        if axis != zero {
+            dot += if (hash >> sign) & 1 == 0 { value } else { -value };
+            sign += 1;
+        }
+    }
+    dot
+}
+
+// Define terrain parameters
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+    pub noise_kind: NoiseKind,
+    pub worley: WorleyOptions,
+    pub width: u32,
+    pub height: u32,
+    // Base frequency of the landmass field; configs saved before the detail field call it `scale`
+    #[serde(alias = "scale")]
+    pub continent_scale: f64,
+    // A second, usually finer, field mixed into the heights by `detail_weight` (0 = continents only)
+    pub detail_scale: f64,
+    pub detail_weight: f64,
+    pub octaves: usize,
+    // Extra amplitude multiplier per octave on top of the persistence falloff. Octaves past the end of the
+    // list get 1.0, and entries past `octaves` are kept so lowering and raising the count loses nothing.
+    pub octave_weights: Vec<f64>,
+    pub persistence: f64,
+    pub lacunarity: f64,
+    pub pixel_size: u32,
+    // Generate and color at this many times the resolution on each axis, then average down to the
+    // output size, for smooth edges between colors
+    pub supersample: u32,
+    pub tileable: bool,
+    pub ridged: bool,
+    // World origin: the point of the noise field at the middle of the map, in units of the map's longer
+    // side at span 1. The falloff stays centered on the map.
+    pub offset_x: f64,
+    pub offset_y: f64,
+    // How much of the noise field the map's longer side covers, in those same units; below 1 zooms in
+    // on the area around the origin and above 1 shows more of the world at the same feature size
+    pub world_span: f64,
+    // Sample 3D noise with `time` as the third axis, so stepping the time morphs the terrain smoothly.
+    // Tileable maps already use four dimensions for the torus and ignore the time.
+    pub animate: bool,
+    pub time: f64,
+    pub warp_strength: f64,
+    pub falloff: f64,
+    // Stretch each generated map so its lowest point is 0.0 and its highest 1.0
+    pub auto_normalize: bool,
+    // Replace each height by the share of the map below it, so every height range covers the same area
+    pub equalize: bool,
+    // Flip the heights (h becomes 1 - h) so peaks turn into trenches and oceans into highlands
+    pub invert: bool,
+    // Clips and stretches the (normalized) heights ahead of the elevation curve
+    pub levels: Levels,
+    // Remaps the (normalized) heights before erosion, terracing and coloring
+    pub elevation_curve: Curve,
+    pub sea_level: f64,
+    // Squeeze heights near the sea level away from it before picking biomes, so the shore bands narrow;
+    // 0 leaves them alone and 1 is a hard cut from water to land
+    pub coastline_sharpness: f64,
+    // Weather slopes steeper than `talus_angle` degrees (with heights scaled by RELIEF) by sliding material downhill
+    pub thermal_erosion: bool,
+    pub thermal_iterations: u32,
+    pub talus_angle: f64,
+    // Droplet erosion, applied on request to the current maps rather than as part of generation
+    pub droplets: u32,
+    pub erosion_rate: f64,
+    pub deposition_rate: f64,
+    pub evaporation: f64,
+    // Quantize heights into this many flat plateaus; 0 leaves them smooth
+    pub terrace_steps: u32,
+    // Pull heights at and above `plateau_level` down onto it, and those just below up towards it, for flat
+    // topped mesas; `plateau_sharpness` blends from untouched (0) to fully flat tops and sheer sides (1)
+    pub plateau_level: f64,
+    pub plateau_sharpness: f64,
+    // How strongly distance from the middle row shifts land biomes towards colder ones; 0 disables it
+    pub latitude_influence: f64,
+    // Paint land above a snow line with the top band instead: the line sits at `snow_level` on the middle
+    // row and drops by `snow_latitude` towards the top and bottom edges
+    pub snow_line: bool,
+    pub snow_level: f64,
+    pub snow_latitude: f64,
+    // Jitter each block's biome color brightness by up to this share of TEXTURE_RANGE, from a hash of its
+    // position and the seed, to break up flat fills; 0 keeps solid colors
+    pub texture_amount: f64,
+    // Partition the land into `region_count` Voronoi regions placed from the seed, each painted in its own
+    // color over the biome colors with `region_opacity`, and optionally outlined along the borders
+    pub regions: bool,
+    pub region_count: u32,
+    pub region_opacity: f64,
+    pub region_borders: bool,
+    pub region_border_color: Color32,
+    // Relief shading from the height gradient; angles are in degrees, azimuth clockwise from north
+    pub hillshade: bool,
+    pub shading_strength: f64,
+    pub light_azimuth: f64,
+    pub light_altitude: f64,
+    // Slope exaggeration for exported normal maps
+    pub normal_strength: f64,
+    // Lines wherever the height crosses a multiple of `contour_interval`
+    pub contours: bool,
+    pub contour_interval: f64,
+    pub contour_color: Color32,
+    // Outline land pixels within `coastline_width` pixels of water, for a drawn map look
+    pub coastline: bool,
+    pub coastline_width: u32,
+    pub coastline_color: Color32,
+    // Land cells that more than `river_threshold` of the map drains through are painted as rivers
+    pub rivers: bool,
+    pub river_threshold: f64,
+    pub river_color: Color32,
+    // Color water by depth below the sea level instead of by band, from the shore to the deepest possible
+    // floor at height 0
+    pub depth_shading: bool,
+    pub shallow_color: Color32,
+    pub deep_color: Color32,
+    // Applied to the finished image, after shading: a brightness offset, contrast around mid-gray, then gamma
+    pub brightness: f64,
+    pub contrast: f64,
+    pub gamma: f64,
+    pub color_mode: ColorMode,
+    // How the preview texture is sampled when zoomed; exports are unaffected
+    pub texture_filter: egui::TextureFilter,
+    // What the preview shows of the maps
+    pub view_mode: ViewMode,
+    pub gradient: Gradient,
+    // Biome bands, checked in order
+    pub bands: Vec<BiomeBand>,
+    // Snap band colors to fully saturated channels, the original look
+    pub posterize: bool,
+    // Land biomes by elevation above sea level (rows, low to high) and moisture (columns, dry to wet)
+    pub biome_matrix: Vec<Vec<BiomeCell>>,
+}
+
+// How the terrain is colored; the bands still name every pixel in all modes
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ColorMode {
+    // Classic single-axis bands by elevation
+    Elevation,
+    // Biome matrix lookup by elevation and a second moisture noise field; water comes from the water bands
+    Biomes,
+    // Smooth color ramp over the whole height range
+    Gradient,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 3] = [ColorMode::Elevation, ColorMode::Biomes, ColorMode::Gradient];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorMode::Elevation => "Elevation Bands",
+            ColorMode::Biomes => "Biome Matrix",
+            ColorMode::Gradient => "Smooth Gradient",
+        }
+    }
+}
+
+// Alternative views of the same maps, switched without generating or recoloring anything
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ViewMode {
+    // The colored terrain
+    Color,
+    // Steepness from dark (flat) to bright (the steepest slope on the map)
+    Slope,
+    // Raw elevation in 8-bit grayscale, from black at 0 to white at 1
+    Heightmap,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 3] = [ViewMode::Color, ViewMode::Slope, ViewMode::Heightmap];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ViewMode::Color => "Color",
+            ViewMode::Slope => "Slope",
+            ViewMode::Heightmap => "Heightmap",
+        }
+    }
+
+    // This view of `maps`, whose colored image is `colored`. Falls back to the colored image when the maps
+    // don't match it, as for an imported PNG.
+    pub fn image<'a>(self, maps: &TerrainMaps, colored: &'a egui::ColorImage) -> Cow<'a, egui::ColorImage> {
+        if maps.size != colored.size {
+            return Cow::Borrowed(colored);
+        }
+        match self {
+            ViewMode::Color => Cow::Borrowed(colored),
+            ViewMode::Slope => Cow::Owned(maps.slope_image()),
+            ViewMode::Heightmap => Cow::Owned(maps.height_image()),
+        }
+    }
+}
+
+// Piecewise-linear transfer function through (input, output) points sorted by input. The first and last
+// segments extend past the ends, so heights outside 0..1 keep the slope there.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Default for Curve {
+    // The identity, which leaves heights unchanged
+    fn default() -> Self {
+        Self {
+            points: vec![(0.0, 0.0), (1.0, 1.0)],
+        }
+    }
+}
+
+impl Curve {
+    pub fn evaluate(&self, input: f64) -> f64 {
+        match self.points.as_slice() {
+            [] => input,
+            [(_, output)] => *output,
+            points => {
+                let segment = points.windows(2).position(|pair| input < pair[1].0).unwrap_or(points.len() - 2);
+                let ((x0, y0), (x1, y1)) = (points[segment], points[segment + 1]);
+                if x1 - x0 <= f64::EPSILON {
+                    return y1;
+                }
+                y0 + (input - x0) * (y1 - y0) / (x1 - x0)
+            }
+        }
+    }
+}
+
+// Image-editor style levels: heights from `input_black` to `input_white` are stretched onto
+// `output_black`..`output_white`, and anything beyond the input points is clipped to the ends
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Levels {
+    pub input_black: f64,
+    pub input_white: f64,
+    pub output_black: f64,
+    pub output_white: f64,
+}
+
+impl Default for Levels {
+    // The identity, which leaves heights unchanged
+    fn default() -> Self {
+        Self {
+            input_black: 0.0,
+            input_white: 1.0,
+            output_black: 0.0,
+            output_white: 1.0,
+        }
+    }
+}
+
+impl Levels {
+    pub fn apply(&self, input: f64) -> f64 {
+        let range = self.input_white - self.input_black;
+        // Input points that meet or cross make a hard threshold at the black point
+        let t = if range <= f64::EPSILON {
+            if input < self.input_black { 0.0 } else { 1.0 }
+        } else {
+            ((input - self.input_black) / range).clamp(0.0, 1.0)
+        };
+        self.output_black + t * (self.output_white - self.output_black)
+    }
+}
+
+// Color space a gradient blends between its stops in
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+    // Physically even mixing; brighter midpoints than sRGB
+    LinearRgb,
+    // Per channel on the stored values, the original behaviour
+    #[default]
+    Srgb,
+    // Around the shorter way of the hue wheel, which keeps blends between complementary colors saturated
+    // instead of passing through gray
+    Hsv,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 3] = [Interpolation::LinearRgb, Interpolation::Srgb, Interpolation::Hsv];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Interpolation::LinearRgb => "Linear RGB",
+            Interpolation::Srgb => "sRGB",
+            Interpolation::Hsv => "HSV",
+        }
+    }
+
+    // `t` = 0 gives `from` and 1 gives `to`
+    fn blend(self, from: Color32, to: Color32, t: f32) -> Color32 {
+        match self {
+            Interpolation::LinearRgb => {
+                let (from, to) = (egui::Rgba::from(from), egui::Rgba::from(to));
+                Color32::from(from * (1.0 - t) + to * t)
+            }
+            Interpolation::Srgb => lerp_color(from, to, t),
+            Interpolation::Hsv => {
+                let (from, to) = (Hsva::from(from), Hsva::from(to));
+                // A gray has no hue of its own, so it takes the other end's instead of swinging through red
+                let (from_hue, to_hue) = match (from.s > 0.0, to.s > 0.0) {
+                    (false, true) => (to.h, to.h),
+                    (true, false) => (from.h, from.h),
+                    _ => (from.h, to.h),
+                };
+                let turn = (to_hue - from_hue + 0.5).rem_euclid(1.0) - 0.5;
+                let lerp = |a: f32, b: f32| a + (b - a) * t;
+                Color32::from(Hsva::new((from_hue + turn * t).rem_euclid(1.0), lerp(from.s, to.s), lerp(from.v, to.v), 1.0))
+            }
+        }
+    }
+}
+
+// Color stops as (position, color), interpolated between neighbours in the chosen color space
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Gradient {
+    pub stops: Vec<(f64, Color32)>,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        let stops = [
+            (0.0, [0, 0, 128]),
+            (0.35, [30, 80, 200]),
+            (0.4, [90, 150, 230]),
+            (0.42, [210, 180, 140]),
+            (0.5, [90, 160, 60]),
+            (0.7, [34, 110, 34]),
+            (0.8, [139, 90, 50]),
+            (0.95, [255, 255, 255]),
+        ];
+        Self {
+            stops: stops.iter().map(|&(position, [r, g, b])| (position, Color32::from_rgb(r, g, b))).collect(),
+            interpolation: Interpolation::default(),
+        }
+    }
+}
+
+impl Gradient {
+    // Heights outside the stops take the nearest end color. The stops don't have to be sorted, so the
+    // editor can reorder them while a handle is being dragged past another
+    pub fn sample(&self, height: f64) -> Color32 {
+        let below = self.stops.iter().filter(|stop| stop.0 <= height).max_by(|a, b| a.0.total_cmp(&b.0));
+        let above = self.stops.iter().filter(|stop| stop.0 > height).min_by(|a, b| a.0.total_cmp(&b.0));
+        match (below, above) {
+            (Some(&(start, from)), Some(&(end, to))) => {
+                self.interpolation.blend(from, to, ((height - start) / (end - start)) as f32)
+            }
+            (Some(&(_, color)), None) | (None, Some(&(_, color))) => color,
+            (None, None) => Color32::BLACK,
+        }
+    }
+
+    pub fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BiomeCell {
+    pub name: String,
+    pub color: Color32,
+}
+
+impl BiomeCell {
+    pub fn new(name: &str, color: [u8; 3]) -> Self {
+        Self {
+            name: name.to_owned(),
+            color: Color32::from_rgb(color[0], color[1], color[2]),
+        }
+    }
+}
+
+// Predefined band lists; all share the classic thresholds and only differ in color
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PalettePreset {
+    // The original palette, posterized
+    Classic,
+    // The original palette without posterizing
+    Natural,
+    // Blue to yellow and orange with distinct lightness, readable with red-green color blindness
+    Deuteranopia,
+    Grayscale,
+}
+
+impl PalettePreset {
+    pub const ALL: [PalettePreset; 4] =
+        [PalettePreset::Classic, PalettePreset::Natural, PalettePreset::Deuteranopia, PalettePreset::Grayscale];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PalettePreset::Classic => "Classic",
+            PalettePreset::Natural => "Natural",
+            PalettePreset::Deuteranopia => "Colorblind Safe",
+            PalettePreset::Grayscale => "Grayscale",
+        }
+    }
+
+    pub fn bands(self) -> Vec<BiomeBand> {
+        let colors: [[u8; 3]; 6] = match self {
+            PalettePreset::Classic | PalettePreset::Natural => {
+                [[0, 0, 255], [65, 105, 225], [210, 180, 140], [34, 139, 34], [139, 69, 19], [255, 255, 255]]
+            }
+            PalettePreset::Deuteranopia => {
+                [[0, 63, 127], [0, 114, 178], [240, 228, 66], [230, 159, 0], [140, 90, 30], [255, 255, 255]]
+            }
+            PalettePreset::Grayscale => {
+                [[20, 20, 20], [60, 60, 60], [120, 120, 120], [160, 160, 160], [200, 200, 200], [255, 255, 255]]
+            }
+        };
+        vec![
+            BiomeBand::new("Deep Water", 0.3, colors[0]).water(),
+            BiomeBand::new("Water", 0.4, colors[1]).water(),
+            BiomeBand::new("Sand", 0.5, colors[2]),
+            BiomeBand::new("Grass", 0.7, colors[3]),
+            BiomeBand::new("Mountain", 0.8, colors[4]),
+            BiomeBand::new("Snow", 1.0, colors[5]),
+        ]
+    }
+}
+
+// A resolved biome for one pixel
+pub struct Biome<'a> {
+    pub name: &'a str,
+    pub color: Color32,
+}
+
+// A named color used for every height from the previous band's threshold (inclusive) up to `threshold`
+// (exclusive), so a height exactly on a boundary belongs to the band above it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BiomeBand {
+    pub name: String,
+    pub threshold: f64,
+    pub color: Color32,
+    // Water bands are only used below the sea level, land bands only above it
+    #[serde(default)]
+    pub water: bool,
+}
+
+impl BiomeBand {
+    pub fn new(name: &str, threshold: f64, color: [u8; 3]) -> Self {
+        Self {
+            name: name.to_owned(),
+            threshold,
+            color: Color32::from_rgb(color[0], color[1], color[2]),
+            water: false,
+        }
+    }
+
+    pub fn water(mut self) -> Self {
+        self.water = true;
+        self
+    }
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            noise_kind: NoiseKind::Perlin,
+            worley: WorleyOptions::default(),
+            width: 512,
+            height: 512,
+            continent_scale: 50.0,
+            detail_scale: 150.0,
+            detail_weight: 0.0,
+            octaves: 6,
+            octave_weights: vec![1.0; 6],
+            persistence: 0.5,
+            lacunarity: 2.0,
+            pixel_size: 1,
+            supersample: 1,
+            tileable: false,
+            ridged: false,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            world_span: 1.0,
+            animate: false,
+            time: 0.0,
+            warp_strength: 0.0,
+            falloff: 0.0,
+            auto_normalize: false,
+            equalize: false,
+            invert: false,
+            levels: Levels::default(),
+            elevation_curve: Curve::default(),
+            sea_level: 0.4,
+            coastline_sharpness: 0.0,
+            thermal_erosion: false,
+            thermal_iterations: 50,
+            talus_angle: 40.0,
+            droplets: 50_000,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            evaporation: 0.01,
+            terrace_steps: 0,
+            plateau_level: 0.7,
+            plateau_sharpness: 0.0,
+            latitude_influence: 0.0,
+            snow_line: false,
+            snow_level: 0.8,
+            snow_latitude: 0.3,
+            texture_amount: 0.0,
+            regions: false,
+            region_count: 12,
+            region_opacity: 0.6,
+            region_borders: true,
+            region_border_color: Color32::from_rgb(40, 20, 20),
+            hillshade: false,
+            shading_strength: 0.5,
+            light_azimuth: 315.0,
+            light_altitude: 45.0,
+            normal_strength: 1.0,
+            contours: false,
+            contour_interval: 0.05,
+            contour_color: Color32::from_rgb(60, 40, 20),
+            coastline: false,
+            coastline_width: 1,
+            coastline_color: Color32::from_rgb(30, 30, 50),
+            rivers: false,
+            river_threshold: 0.002,
+            river_color: Color32::from_rgb(65, 105, 225),
+            depth_shading: false,
+            shallow_color: Color32::from_rgb(110, 180, 225),
+            deep_color: Color32::from_rgb(10, 30, 90),
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            color_mode: ColorMode::Elevation,
+            texture_filter: egui::TextureFilter::Nearest,
+            view_mode: ViewMode::Color,
+            gradient: Gradient::default(),
+            bands: PalettePreset::Classic.bands(),
+            posterize: true,
+            biome_matrix: TerrainConfig::default_biome_matrix(),
+        }
+    }
+}
+
+impl TerrainConfig {
+    // The same map at `factor` times the resolution, covering the same world area. Blocky maps keep their
+    // blocks the same size on the output, so they are sampled just as before; only unblocked maps gain
+    // samples. The coastline widens with it so it is as thick on the output.
+    fn supersampled(&self, factor: usize) -> TerrainConfig {
+        let factor = factor as u32;
+        TerrainConfig {
+            width: self.width * factor,
+            height: self.height * factor,
+            pixel_size: if self.pixel_size > 1 { self.pixel_size * factor } else { 1 },
+            coastline_width: self.coastline_width.max(1) * factor,
+            supersample: 1,
+            ..self.clone()
+        }
+    }
+
+    // How many octaves of the landmass field are coarser than the sampling grid: octave k has
+    // continent_scale × world_span × lacunarity^k cycles across the longer side, and once that reaches the
+    // number of samples there, counting blocks and supersampling, it only adds aliasing and time. Always at
+    // least one.
+    pub fn useful_octaves(&self) -> usize {
+        let samples = (self.width.max(self.height) * self.supersample.max(1)) as f64 / self.pixel_size.max(1) as f64;
+        let mut cycles = self.continent_scale * self.world_span;
+        if cycles <= 0.0 {
+            return usize::MAX;
+        }
+        if self.lacunarity <= 1.0 {
+            return if cycles < samples { usize::MAX } else { 1 };
+        }
+        let mut useful = 0;
+        while cycles < samples {
+            useful += 1;
+            cycles *= self.lacunarity;
+        }
+        useful.max(1)
+    }
+
+    fn default_biome_matrix() -> Vec<Vec<BiomeCell>> {
+        vec![
+            vec![
+                BiomeCell::new("Desert", [237, 201, 175]),
+                BiomeCell::new("Grassland", [124, 176, 66]),
+                BiomeCell::new("Swamp", [74, 93, 62]),
+            ],
+            vec![
+                BiomeCell::new("Shrubland", [160, 150, 90]),
+                BiomeCell::new("Forest", [34, 110, 34]),
+                BiomeCell::new("Rainforest", [20, 80, 40]),
+            ],
+            vec![
+                BiomeCell::new("Rock", [128, 128, 128]),
+                BiomeCell::new("Tundra", [170, 180, 160]),
+                BiomeCell::new("Snow", [255, 255, 255]),
+            ],
+        ]
+    }
+
+    // Whether a height is flooded: below the sea level, or anywhere once the sea reaches the last band
+    pub fn underwater(&self, height: f64) -> bool {
+        let top_floor = match self.bands.len() {
+            0 | 1 => 0.0,
+            count => self.bands[count - 2].threshold,
+        };
+        height < self.sea_level || self.sea_level >= top_floor
+    }
+
+    // The band a height falls in; see BiomeBand for how boundaries are split
+    fn band_index(&self, height: f64) -> usize {
+        // Bands are kept sorted, so this counts the thresholds at or below the height
+        self.bands.partition_point(|band| band.threshold <= height)
+    }
+
+    // Pick the band the height falls in; anything at or above the last band's threshold uses it. Below
+    // the sea level a land band is replaced by the shallowest water band, and from the sea level up a
+    // water band is replaced by the lowest land band, so the sea level alone decides what is flooded and
+    // a height exactly at it is land. A sea level at or above the lower bound of the last band (the snow
+    // line in the presets) floods the whole map, peaks included.
+    fn biome_at(&self, height: f64) -> Option<&BiomeBand> {
+        let band = self.bands.get(self.band_index(height)).or(self.bands.last())?;
+        let underwater = self.underwater(height);
+        if band.water == underwater {
+            return Some(band);
+        }
+        let replacement = if underwater {
+            self.bands.iter().rev().find(|band| band.water)
+        } else {
+            self.bands.iter().find(|band| !band.water)
+        };
+        Some(replacement.unwrap_or(band))
+    }
+
+    // Resolve the biome at a pixel. The name always comes from the bands, and so does the color of water
+    // outside gradient mode; in biome matrix mode land is looked up by how far above sea level it is and
+    // by moisture.
+    pub fn biome(&self, height: f64, moisture: f64) -> Option<Biome<'_>> {
+        let band = self.biome_at(height)?;
+        if self.color_mode == ColorMode::Gradient {
+            return Some(Biome { name: &band.name, color: self.gradient.sample(height) });
+        }
+        if self.color_mode == ColorMode::Elevation || band.water {
+            return Some(Biome { name: &band.name, color: self.band_color(band) });
+        }
+        // Rows and columns split their range evenly, each including its lower edge like the bands
+        let rows = self.biome_matrix.len();
+        let land = ((height - self.sea_level) / (1.0 - self.sea_level)).clamp(0.0, 1.0);
+        let row = self.biome_matrix.get(((land * rows as f64) as usize).min(rows.saturating_sub(1)))?;
+        let column = ((moisture.clamp(0.0, 1.0) * row.len() as f64) as usize).min(row.len().saturating_sub(1));
+        let cell = row.get(column)?;
+        Some(Biome { name: &cell.name, color: cell.color })
+    }
+
+    // The height used to pick a land biome: raised towards the top and bottom rows and lowered in the
+    // middle, so the poles get more snow and the equator more lowland. Water is left alone and land is
+    // never pushed below the sea level, so the coastline stays where it is.
+    pub fn climate_height(&self, height: f64, latitude: f64) -> f64 {
+        if self.latitude_influence == 0.0 || self.underwater(height) {
+            return height;
+        }
+        let polar = (latitude * 2.0 - 1.0).abs();
+        (height + self.latitude_influence * (polar - 0.5)).max(self.sea_level)
+    }
+
+    // Remap heights within COAST_BAND of the sea level by a signed power of their offset from it, which
+    // steepens the profile there. The ends of the band stay put, so heights outside it are unchanged.
+    pub fn sharpen_coast(&self, height: f64) -> f64 {
+        let offset = (height - self.sea_level) / COAST_BAND;
+        if self.coastline_sharpness <= 0.0 || offset.abs() >= 1.0 {
+            return height;
+        }
+        let exponent = 1.0 - self.coastline_sharpness.min(1.0);
+        let sharpened = if exponent <= 0.0 { offset.signum() } else { offset.signum() * offset.abs().powf(exponent) };
+        self.sea_level + sharpened * COAST_BAND
+    }
+
+    // The biome at one pixel of `maps`, with the coastline sharpened and the latitude shift and snow line
+    // applied. Snow uses the top band, which is Snow in every preset.
+    pub fn pixel_biome(&self, maps: &TerrainMaps, index: usize) -> Option<Biome<'_>> {
+        let (height, latitude) = (self.sharpen_coast(maps.heights[index]), maps.latitude(index));
+        if self.snow_line && !self.underwater(height) && height >= self.snow_level - self.snow_latitude * (latitude * 2.0 - 1.0).abs() {
+            let band = self.bands.last()?;
+            return Some(Biome { name: &band.name, color: self.band_color(band) });
+        }
+        self.biome(self.climate_height(height, latitude), maps.moisture(index))
+    }
+
+    // How a band is drawn outside gradient mode; the classic look posterizes every band
+    pub fn band_color(&self, band: &BiomeBand) -> Color32 {
+        if !self.posterize {
+            return band.color;
+        }
+        Self::quantize_color([band.color.r(), band.color.g(), band.color.b()], 1)
+    }
+
+    // Clamp band thresholds into 0..=1 and order the bands by them, as biome lookup expects; bands with
+    // equal thresholds keep their order. Configs loaded from files may break either.
+    pub fn sort_bands(&mut self) {
+        for band in &mut self.bands {
+            band.threshold = if band.threshold.is_nan() { 1.0 } else { band.threshold.clamp(0.0, 1.0) };
+        }
+        self.bands.sort_by(|a, b| a.threshold.total_cmp(&b.threshold));
+    }
+
+    // Insert a band below band `index`, halfway between its threshold and the previous band's (or 0),
+    // colored halfway between the two and on the same side of the sea
+    pub fn insert_band(&mut self, index: usize) {
+        let Some(above) = self.bands.get(index) else {
+            self.bands.push(BiomeBand::new("New Band", 1.0, [255, 255, 255]));
+            return;
+        };
+        let below = index.checked_sub(1).map(|previous| &self.bands[previous]);
+        let floor = below.map_or(0.0, |band| band.threshold);
+        let color = below.map_or(above.color, |band| lerp_color(band.color, above.color, 0.5));
+        let band = BiomeBand {
+            name: "New Band".to_owned(),
+            threshold: (floor + above.threshold) / 2.0,
+            color,
+            water: above.water,
+        };
+        self.bands.insert(index, band);
+    }
+
+    // Replace the bands with a preset's, keeping everything else
+    pub fn apply_palette(&mut self, preset: PalettePreset) {
+        self.bands = preset.bands();
+        self.posterize = preset == PalettePreset::Classic;
+    }
+
+    // Share of the map each biome covers, as (name, color, fraction), largest first. Biomes are told apart by
+    // name, so matrix cells or bands that share a name are counted together.
+    pub fn biome_shares(&self, maps: &TerrainMaps) -> Vec<(String, Color32, f64)> {
+        let counts = (0..maps.heights.len())
+            .into_par_iter()
+            .fold(HashMap::new, |mut counts: HashMap<&str, (Color32, usize)>, index| {
+                if let Some(biome) = self.pixel_biome(maps, index) {
+                    counts.entry(biome.name).or_insert((biome.color, 0)).1 += 1;
+                }
+                counts
+            })
+            .reduce(HashMap::new, |mut total, counts| {
+                for (name, (color, count)) in counts {
+                    total.entry(name).or_insert((color, 0)).1 += count;
+                }
+                total
+            });
+        let pixels = maps.heights.len().max(1) as f64;
+        let mut shares: Vec<(String, Color32, f64)> =
+            counts.into_iter().map(|(name, (color, count))| (name.to_owned(), color, count as f64 / pixels)).collect();
+        shares.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        shares
+    }
+
+    pub fn get_terrain_color(&self, maps: &TerrainMaps, index: usize) -> Color32 {
+        self.pixel_biome(maps, index).map_or(Color32::BLACK, |biome| biome.color)
+    }
+
+    // Posterize each channel into `levels` + 1 values. This is purely a color effect; the spatial
+    // block size comes from `pixel_size` and is applied while sampling the heights.
+    fn quantize_color(color: [u8; 3], levels: u32) -> Color32 {
+        let quantize = |v: u8| {
+            let step = 255 / levels;
+            ((v as f32 / step as f32).round() * step as f32) as u8
+        };
+
+        Color32::from_rgb(
+            quantize(color[0]),
+            quantize(color[1]),
+            quantize(color[2]),
+        )
+    }
+}
+
+// Per-pixel data a terrain image is colored from, row-major at the generated resolution
+#[derive(Clone, Default)]
+pub struct TerrainMaps {
+    pub size: [usize; 2],
+    // Normalized heights, roughly 0..1
+    pub heights: Vec<f64>,
+    // Moisture in 0..1; empty unless the biome matrix is in use
+    pub moisture: Vec<f64>,
+    // Fraction of the map draining through each pixel; empty unless rivers are enabled
+    pub flow: Vec<f64>,
+    // World seed the maps were generated from, which also seeds the color texture
+    pub seed: u32,
+    // The full-resolution maps behind a supersampled image, kept so recoloring stays smooth; the maps
+    // above are their block averages
+    pub fine: Option<Arc<TerrainMaps>>,
+}
+
+impl TerrainMaps {
+    pub fn moisture(&self, index: usize) -> f64 {
+        self.moisture.get(index).copied().unwrap_or(0.5)
+    }
+
+    pub fn flow(&self, index: usize) -> f64 {
+        self.flow.get(index).copied().unwrap_or(0.0)
+    }
+
+    // Recompute `flow` from the current heights, or clear it when rivers are off
+    pub fn update_flow(&mut self, config: &TerrainConfig) {
+        self.flow = if config.rivers {
+            rivers::flow_accumulation(&self.heights, self.size)
+        } else {
+            Vec::new()
+        };
+    }
+
+    // Average `factor`×`factor` blocks, except drainage, which keeps each block's largest flow so rivers
+    // don't thin out
+    fn downsampled(&self, factor: usize) -> TerrainMaps {
+        let size = [self.size[0] / factor, self.size[1] / factor];
+        let reduce = |values: &[f64], combine: fn(&[f64]) -> f64| -> Vec<f64> {
+            if values.is_empty() {
+                return Vec::new();
+            }
+            (0..size[0] * size[1])
+                .into_par_iter()
+                .map(|index| {
+                    let (x, y) = (index % size[0] * factor, index / size[0] * factor);
+                    let block: Vec<f64> = (0..factor * factor).map(|i| values[(y + i / factor) * self.size[0] + x + i % factor]).collect();
+                    combine(&block)
+                })
+                .collect()
+        };
+        let mean = |block: &[f64]| block.iter().sum::<f64>() / block.len() as f64;
+        let max = |block: &[f64]| block.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        TerrainMaps {
+            size,
+            heights: reduce(&self.heights, mean),
+            moisture: reduce(&self.moisture, mean),
+            flow: reduce(&self.flow, max),
+            seed: self.seed,
+            fine: None,
+        }
+    }
+
+    // Row position of a pixel from 0 (top) to 1 (bottom)
+    pub fn latitude(&self, index: usize) -> f64 {
+        (index / self.size[0].max(1)) as f64 / self.size[1].saturating_sub(1).max(1) as f64
+    }
+
+    // Pixel counts per equal-width height bin over 0..1; heights outside that range land in the end bins
+    pub fn histogram(&self, bins: usize) -> Vec<usize> {
+        let mut counts = vec![0; bins];
+        if bins == 0 {
+            return counts;
+        }
+        for &height in &self.heights {
+            let bin = (height.clamp(0.0, 1.0) * bins as f64) as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+        counts
+    }
+
+    // Central-difference height gradient, treating the map as a unit square so slopes don't depend on resolution
+    pub fn gradient(&self, x: usize, y: usize) -> [f64; 2] {
+        let [width, height] = self.size;
+        let at = |x: usize, y: usize| self.heights[y * width + x];
+        let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+        let dx = (at(right, y) - at(left, y)) / ((right - left).max(1) as f64 / width as f64);
+        let dy = (at(x, down) - at(x, up)) / ((down - up).max(1) as f64 / height as f64);
+        [dx, dy]
+    }
+
+    // Heights as 8-bit gray; heights are normalized to 0..=1, so nothing needs stretching
+    pub fn height_image(&self) -> egui::ColorImage {
+        let pixels = self.heights.par_iter().map(|height| Color32::from_gray((height.clamp(0.0, 1.0) * 255.0).round() as u8)).collect();
+        egui::ColorImage { size: self.size, pixels }
+    }
+
+    // Grayscale slope angle, with heights scaled by RELIEF as for shading, stretched so the steepest pixel
+    // is white; a flat map stays black
+    pub fn slope_image(&self) -> egui::ColorImage {
+        let width = self.size[0];
+        let angles: Vec<f64> = (0..self.heights.len())
+            .into_par_iter()
+            .map(|index| {
+                let [dx, dy] = self.gradient(index % width, index / width);
+                ((dx * dx + dy * dy).sqrt() * RELIEF).atan()
+            })
+            .collect();
+        let steepest = angles.iter().copied().fold(0.0, f64::max);
+        let scale = if steepest > f64::EPSILON { 255.0 / steepest } else { 0.0 };
+        let pixels = angles.iter().map(|angle| Color32::from_gray((angle * scale).round() as u8)).collect();
+        egui::ColorImage { size: self.size, pixels }
+    }
+
+    // Whether a contour level lies between this pixel and its right or lower neighbor
+    pub fn crosses_contour(&self, x: usize, y: usize, interval: f64) -> bool {
+        let [width, height] = self.size;
+        let level = |x: usize, y: usize| (self.heights[y * width + x] / interval).floor();
+        let here = level(x, y);
+        (x + 1 < width && level(x + 1, y) != here) || (y + 1 < height && level(x, y + 1) != here)
+    }
+
+    // Whether a land pixel has water within `reach` pixels in any direction, counting diagonals as one step
+    pub fn on_coast(&self, x: usize, y: usize, config: &TerrainConfig, reach: usize) -> bool {
+        let [width, height] = self.size;
+        if config.underwater(self.heights[y * width + x]) {
+            return false;
+        }
+        let rows = y.saturating_sub(reach)..=(y + reach).min(height - 1);
+        let columns = x.saturating_sub(reach)..=(x + reach).min(width - 1);
+        rows.flat_map(|ny| columns.clone().map(move |nx| ny * width + nx)).any(|index| config.underwater(self.heights[index]))
+    }
+
+    // Unit surface normal with the height range scaled by RELIEF times `strength`
+    pub fn normal(&self, x: usize, y: usize, strength: f64) -> [f64; 3] {
+        let [dx, dy] = self.gradient(x, y);
+        let relief = RELIEF * strength;
+        let normal = [-dx * relief, -dy * relief, 1.0];
+        let length = (normal[0] * normal[0] + normal[1] * normal[1] + 1.0).sqrt();
+        [normal[0] / length, normal[1] / length, normal[2] / length]
+    }
+}
+
+// Heights read from an image, which replace the noise when colorizing an existing heightmap
+pub struct Heightmap {
+    pub size: [usize; 2],
+    // In 0..1, row-major
+    pub heights: Vec<f64>,
+}
+
+impl Heightmap {
+    // Bilinearly resampled to `size`, so the map can be generated at any size
+    fn resampled(&self, size: [usize; 2]) -> Vec<f64> {
+        let [width, height] = self.size;
+        if size == self.size || width == 0 || height == 0 {
+            return self.heights.clone();
+        }
+        let scale = [width as f64 / size[0] as f64, height as f64 / size[1] as f64];
+        (0..size[0] * size[1])
+            .into_par_iter()
+            .map(|index| {
+                // Pixel centers line up, so every source pixel keeps an even share
+                let x = ((index % size[0]) as f64 + 0.5) * scale[0] - 0.5;
+                let y = ((index / size[0]) as f64 + 0.5) * scale[1] - 0.5;
+                let (x0, y0) = (x.floor().clamp(0.0, (width - 1) as f64), y.floor().clamp(0.0, (height - 1) as f64));
+                let (tx, ty) = ((x - x0).clamp(0.0, 1.0), (y - y0).clamp(0.0, 1.0));
+                let (x0, y0) = (x0 as usize, y0 as usize);
+                let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                let at = |x: usize, y: usize| self.heights[y * width + x];
+                let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * tx;
+                let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * tx;
+                top + (bottom - top) * ty
+            })
+            .collect()
+    }
+}
+
+// Output of a generation job: the sampled maps and the colored image built from them
+pub struct GeneratedTerrain {
+    pub maps: TerrainMaps,
+    pub image: egui::ColorImage,
+}
+
+// Rows finished by a generation job, shared with the UI thread for a progress bar. Each job also holds
+// the epoch it was started in; once a newer job bumps the shared counter, this one is stale and stops.
+// A paused job waits at its next step until it is resumed or goes stale.
+#[derive(Default)]
+pub struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+    epoch: Arc<AtomicU64>,
+    token: u64,
+    paused: AtomicBool,
+}
+
+impl Progress {
+    // Start a new epoch, making every job started in an earlier one stale
+    pub fn for_epoch(epoch: &Arc<AtomicU64>) -> Self {
+        let token = epoch.fetch_add(1, Ordering::Relaxed) + 1;
+        Self { epoch: Arc::clone(epoch), token, ..Self::default() }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.epoch.load(Ordering::Relaxed) != self.token
+    }
+
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        self.done.load(Ordering::Relaxed) as f32 / total as f32
+    }
+
+    // Steps done and the total, such as batches of erosion droplets
+    pub fn steps(&self) -> (usize, usize) {
+        (self.done.load(Ordering::Relaxed), self.total.load(Ordering::Relaxed))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    fn start(&self, total: usize) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn advance(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // For loops that can stop early: whether to keep going
+    fn advance_unless_stale(&self) -> bool {
+        self.advance();
+        while self.is_paused() && !self.is_stale() {
+            thread::sleep(PAUSE_POLL);
+        }
+        !self.is_stale()
+    }
+}
+
+pub fn generate_terrain(config: &TerrainConfig, seed: u32) -> GeneratedTerrain {
+    generate_terrain_with_progress(config, seed, &Progress::default()).expect("a job outside any epoch never goes stale")
+}
+
+// None if a newer job made this one stale before it finished
+pub fn generate_terrain_with_progress(config: &TerrainConfig, seed: u32, progress: &Progress) -> Option<GeneratedTerrain> {
+    let factor = config.supersample.max(1) as usize;
+    let maps = if factor > 1 {
+        let fine = generate_maps(&config.supersampled(factor), None, seed, progress)?;
+        let coarse = fine.downsampled(factor);
+        TerrainMaps { fine: Some(Arc::new(fine)), ..coarse }
+    } else {
+        generate_maps(config, None, seed, progress)?
+    };
+    let image = color_terrain(config, &maps);
+    Some(GeneratedTerrain { maps, image })
+}
+
+// Build the maps on an imported heightmap instead of the noise, resized to the configured size. The
+// heights still go through the same shaping as generated ones, and the seed still drives moisture and
+// texture. Supersampling and the block size are ignored, since the heights have a resolution of their own.
+pub fn colorize_heightmap(config: &TerrainConfig, heightmap: &Heightmap, seed: u32, progress: &Progress) -> Option<GeneratedTerrain> {
+    let maps = generate_maps(config, Some(heightmap), seed, progress)?;
+    let image = color_terrain(config, &maps);
+    Some(GeneratedTerrain { maps, image })
+}
+
+fn generate_maps(config: &TerrainConfig, heightmap: Option<&Heightmap>, seed: u32, progress: &Progress) -> Option<TerrainMaps> {
+    let with_moisture = config.color_mode == ColorMode::Biomes;
+    let fields = usize::from(heightmap.is_none()) + usize::from(with_moisture);
+    let erosion_steps = if config.thermal_erosion { config.thermal_iterations as usize } else { 0 };
+    progress.start(fields * config.height.div_ceil(config.pixel_size.max(1)) as usize + erosion_steps);
+
+    let heights = match heightmap {
+        Some(heightmap) => shape_heights(config, heightmap.resampled([config.width as usize, config.height as usize]), progress)?,
+        None => generate_heights(config, seed, progress)?,
+    };
+    let moisture = if with_moisture {
+        generate_moisture(config, seed, progress)?
+    } else {
+        Vec::new()
+    };
+    let mut maps = TerrainMaps {
+        size: [config.width as usize, config.height as usize],
+        heights,
+        moisture,
+        flow: Vec::new(),
+        seed,
+        fine: None,
+    };
+    if progress.is_stale() {
+        return None;
+    }
+    maps.update_flow(config);
+    Some(maps)
+}
+
+// Run droplet erosion over already generated maps and recolor the result. Droplet start positions come
+// from the seed, so the same terrain always erodes the same way. Every EROSION_PREVIEW_STEPS batches the
+// heights so far are colored and handed to `preview`; river flow is only recomputed for the final result.
+pub fn erode_terrain(
+    config: &TerrainConfig,
+    mut maps: TerrainMaps,
+    seed: u32,
+    progress: &Progress,
+    mut preview: impl FnMut(GeneratedTerrain),
+) -> Option<GeneratedTerrain> {
+    progress.start(config.droplets.div_ceil(erosion::DROPLET_BATCH) as usize);
+    let params = erosion::HydraulicParams {
+        droplets: config.droplets,
+        erosion_rate: config.erosion_rate,
+        deposition_rate: config.deposition_rate,
+        evaporation: config.evaporation,
+    };
+    // Only the output-resolution heights are eroded, so a supersampled image is no longer smoothed
+    maps.fine = None;
+    let mut snapshot = maps.clone();
+    let mut batches = 0;
+    erosion::hydraulic(&mut maps.heights, maps.size, &params, seed, |heights| {
+        batches += 1;
+        if batches % EROSION_PREVIEW_STEPS == 0 {
+            snapshot.heights.copy_from_slice(heights);
+            preview(GeneratedTerrain { image: color_terrain(config, &snapshot), maps: snapshot.clone() });
+        }
+        progress.advance_unless_stale()
+    });
+    if progress.is_stale() {
+        return None;
+    }
+    maps.update_flow(config);
+    let image = color_terrain(config, &maps);
+    Some(GeneratedTerrain { maps, image })
+}
+
+// Evaluates the height field at continuous pixel coordinates
+struct HeightSampler<'a> {
+    config: &'a TerrainConfig,
+    scale: f64,
+    noise_2d: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    noise_3d: Box<dyn NoiseFn<f64, 3> + Send + Sync>,
+    noise_4d: Box<dyn NoiseFn<f64, 4> + Send + Sync>,
+}
+
+impl<'a> HeightSampler<'a> {
+    fn new(config: &'a TerrainConfig, seed: u32, scale: f64) -> Self {
+        Self {
+            config,
+            scale,
+            noise_2d: config.noise_kind.build::<2>(seed, config.worley),
+            noise_3d: config.noise_kind.build::<3>(seed, config.worley),
+            noise_4d: config.noise_kind.build::<4>(seed, config.worley),
+        }
+    }
+
+    // One noise sample at `frequency` times the base scale. Both axes are measured in world units, the
+    // map's longer side at span 1, so non-square maps aren't stretched; offsets use the same units and
+    // add to the world origin. Time is scaled by the frequency too, so finer octaves change faster.
+    fn noise(&self, x: f64, y: f64, frequency: f64, offset: [f64; 2]) -> f64 {
+        let offset = [offset[0] + self.config.offset_x, offset[1] + self.config.offset_y];
+        let (width, height) = (self.config.width as f64, self.config.height as f64);
+        let longest = width.max(height);
+        let span = self.config.world_span;
+        let (extent_x, extent_y) = (width / longest * span, height / longest * span);
+        let scale = self.scale;
+        if self.config.tileable {
+            // Walk a circle per axis so the noise wraps on a torus; each circumference matches the
+            // span that axis covers in the flat mapping
+            let (sin_x, cos_x) = (TAU * x / width + TAU * offset[0] / extent_x).sin_cos();
+            let (sin_y, cos_y) = (TAU * y / height + TAU * offset[1] / extent_y).sin_cos();
+            let radius_x = frequency * scale * extent_x / TAU;
+            let radius_y = frequency * scale * extent_y / TAU;
+            self.noise_4d.get([radius_x * cos_x, radius_x * sin_x, radius_y * cos_y, radius_y * sin_y])
+        } else {
+            let nx = x / longest * span - 0.5 * extent_x + offset[0];
+            let ny = y / longest * span - 0.5 * extent_y + offset[1];
+            if self.config.animate {
+                return self.noise_3d.get([nx * frequency * scale, ny * frequency * scale, self.config.time * frequency]);
+            }
+            self.noise_2d.get([nx * frequency * scale, ny * frequency * scale])
+        }
+    }
+
+    // Warped fractal noise remapped to roughly 0..1, before any map-level shaping
+    fn fbm(&self, x: f64, y: f64) -> f64 {
+        let config = self.config;
+
+        // Domain warping: displace the lookup by a second field sampled at the base scale. A strength
+        // of 1 moves points by up to about one base feature width.
+        let warp = if config.warp_strength > 0.0 {
+            let qx = self.noise(x, y, 1.0, [0.0, 0.0]);
+            let qy = self.noise(x, y, 1.0, WARP_OFFSET);
+            [config.warp_strength * qx / self.scale, config.warp_strength * qy / self.scale]
+        } else {
+            [0.0, 0.0]
+        };
+
+        let mut noise_value = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut total_amplitude = 0.0;
+
+        for octave in 0..config.octaves {
+            let sample = self.noise(x, y, frequency, warp);
+            // Folding the sample around zero turns its zero crossings into sharp ridges
+            let sample = if config.ridged { 1.0 - sample.abs() } else { sample };
+            let weighted = amplitude * config.octave_weights.get(octave).copied().unwrap_or(1.0);
+            noise_value += sample * weighted;
+            total_amplitude += weighted;
+
+            amplitude *= config.persistence;
+            frequency *= config.lacunarity;
+        }
+
+        if config.ridged {
+            // Ridged octaves are each in 0..1, so the weighted average already is too; with every weight at
+            // zero there is nothing to average
+            if total_amplitude > 0.0 {
+                noise_value / total_amplitude
+            } else {
+                0.0
+            }
+        } else {
+            (noise_value + 1.0) / 2.0
+        }
+    }
+}
+
+// Sample the noise for every pixel, row-major, with values normalized to roughly 0..1
+fn generate_heights(config: &TerrainConfig, seed: u32, progress: &Progress) -> Option<Vec<f64>> {
+    let continents = HeightSampler::new(config, seed, config.continent_scale);
+    let detail = (config.detail_weight > 0.0)
+        .then(|| HeightSampler::new(config, seed.wrapping_add(DETAIL_SEED_OFFSET), config.detail_scale));
+    let heights = sample_field(config, progress, |x, y| {
+        let mut noise_value = continents.fbm(x, y);
+        if let Some(detail) = &detail {
+            noise_value += (detail.fbm(x, y) - noise_value) * config.detail_weight;
+        }
+        noise_value - island_falloff(config, x, y)
+    })?;
+    shape_heights(config, heights, progress)
+}
+
+// Everything done to the heights once they exist, whether sampled from the noise or imported
+fn shape_heights(config: &TerrainConfig, mut heights: Vec<f64>, progress: &Progress) -> Option<Vec<f64>> {
+    if config.auto_normalize {
+        normalize_range(&mut heights);
+    }
+    if config.equalize {
+        equalize(&mut heights);
+    }
+    // After normalizing, so the flipped heights still span 0..1, and before the curve, so it shapes the
+    // inverted terrain
+    if config.invert {
+        for height in &mut heights {
+            *height = 1.0 - *height;
+        }
+    }
+    // The identity is skipped rather than applied, since it would still clip heights outside 0..1
+    if config.levels != Levels::default() {
+        for height in &mut heights {
+            *height = config.levels.apply(*height);
+        }
+    }
+    if config.elevation_curve != Curve::default() {
+        for height in &mut heights {
+            *height = config.elevation_curve.evaluate(*height);
+        }
+    }
+    if config.plateau_sharpness > 0.0 {
+        let level = config.plateau_level;
+        for height in &mut heights {
+            let pull = smoothstep(level - PLATEAU_BAND, level, *height) * config.plateau_sharpness;
+            *height += (level - *height) * pull;
+        }
+    }
+    if config.thermal_erosion {
+        // The largest height step between neighboring pixels that stays below the talus angle
+        let longest = config.width.max(config.height) as f64;
+        let talus = config.talus_angle.to_radians().tan() / (RELIEF * longest);
+        let size = [config.width as usize, config.height as usize];
+        erosion::thermal(&mut heights, size, config.thermal_iterations, talus, || progress.advance_unless_stale());
+        if progress.is_stale() {
+            return None;
+        }
+    }
+    if config.terrace_steps > 0 {
+        let steps = config.terrace_steps as f64;
+        for height in &mut heights {
+            *height = (*height * steps).floor() / steps;
+        }
+    }
+    Some(heights)
+}
+
+// Hermite step from 0 at `edge0` to 1 at `edge1`
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// How far to sink a pixel so landmasses end up surrounded by ocean
+fn island_falloff(config: &TerrainConfig, x: f64, y: f64) -> f64 {
+    let nx = x / config.width as f64 - 0.5;
+    let ny = y / config.height as f64 - 0.5;
+    let edge_distance = (nx * 2.0).abs().max((ny * 2.0).abs());
+    config.falloff * edge_distance.powf(FALLOFF_POWER)
+}
+
+// Moisture is an independent field from a fixed seed offset, stretched to the full 0..1 range so every
+// biome matrix column gets used
+fn generate_moisture(config: &TerrainConfig, seed: u32, progress: &Progress) -> Option<Vec<f64>> {
+    let sampler = HeightSampler::new(config, seed.wrapping_add(MOISTURE_SEED_OFFSET), config.continent_scale);
+    let mut moisture = sample_field(config, progress, |x, y| sampler.fbm(x, y))?;
+    normalize_range(&mut moisture);
+    Some(moisture)
+}
+
+// Evaluate `sample` over the whole map. It runs once per pixel_size block, at the block's top-left
+// pixel, and the value is repeated over the block. Each sampled row advances `progress`; once the job
+// goes stale the remaining rows are skipped and None is returned.
+fn sample_field(config: &TerrainConfig, progress: &Progress, sample: impl Fn(f64, f64) -> f64 + Sync) -> Option<Vec<f64>> {
+    let (width, height) = (config.width, config.height);
+    let block = config.pixel_size.max(1);
+    let (grid_width, grid_height) = (width.div_ceil(block), height.div_ceil(block));
+
+    // Rows are sampled in parallel; collecting keeps them in the same order as a sequential pass
+    let sample = &sample;
+    let grid: Vec<f64> = (0..grid_height)
+        .into_par_iter()
+        .flat_map_iter(|grid_y| {
+            if progress.is_stale() {
+                return Vec::new();
+            }
+            let row: Vec<f64> = (0..grid_width)
+                .map(|grid_x| sample((grid_x * block) as f64, (grid_y * block) as f64))
+                .collect();
+            progress.advance();
+            row
+        })
+        .collect();
+    if progress.is_stale() {
+        return None;
+    }
+
+    if block == 1 {
+        return Some(grid);
+    }
+    Some(
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| grid[(y / block * grid_width + x / block) as usize])
+            .collect(),
+    )
+}
+
+// Remap so the lowest value becomes 0.0 and the highest 1.0; a flat map is left as it is
+fn normalize_range(values: &mut [f64]) {
+    let (min, max) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &h| (min.min(h), max.max(h)));
+    let range = max - min;
+    if range <= f64::EPSILON {
+        return;
+    }
+    for value in values.iter_mut() {
+        *value = (*value - min) / range;
+    }
+}
+
+// Histogram equalization: remap values through their cumulative distribution onto 0..1. Equal values share
+// the middle of their rank range, and a (nearly) constant field is left alone rather than divided by zero.
+fn equalize(values: &mut [f64]) {
+    let mut sorted = values.to_vec();
+    sorted.par_sort_unstable_by(f64::total_cmp);
+    let (Some(&low), Some(&high)) = (sorted.first(), sorted.last()) else {
+        return;
+    };
+    if high - low <= f64::EPSILON {
+        return;
+    }
+    let last_rank = (sorted.len() - 1) as f64;
+    values.par_iter_mut().for_each(|value| {
+        let below = sorted.partition_point(|&other| other < *value);
+        let through = sorted.partition_point(|&other| other <= *value);
+        *value = (below + through - 1) as f64 / (2.0 * last_rank);
+    });
+}
+
+// Map each height to its biome color, optionally shaded by the slope facing the light
+pub fn color_terrain(config: &TerrainConfig, maps: &TerrainMaps) -> egui::ColorImage {
+    let mut image = color_terrain_unadjusted(config, maps);
+    adjust_image(config, &mut image);
+    image
+}
+
+// Everything in `color_terrain` but the final brightness, contrast and gamma pass. Supersampled maps are
+// colored at full resolution and averaged down.
+pub fn color_terrain_unadjusted(config: &TerrainConfig, maps: &TerrainMaps) -> egui::ColorImage {
+    if let Some(fine) = &maps.fine {
+        let factor = fine.size[0] / maps.size[0].max(1);
+        return downsample_image(&color_terrain_unadjusted(&config.supersampled(factor), fine), factor);
+    }
+    let light = light_direction(config.light_azimuth, config.light_altitude);
+    // Flat ground keeps its color; slopes facing toward or away from the light get brighter or darker
+    let flat = light[2];
+    let width = maps.size[0];
+    let regions = config.regions.then(|| {
+        let regions = Regions::new(config.region_count as usize, maps.size, maps.seed.wrapping_add(REGION_SEED_OFFSET));
+        regions.assign(maps.size, config.pixel_size.max(1) as usize)
+    });
+    let pixels: Vec<Color32> = maps
+        .heights
+        .par_iter()
+        .enumerate()
+        .map(|(index, &h)| {
+            let (x, y) = (index % width, index / width);
+            let mut color = config.get_terrain_color(maps, index);
+            if config.depth_shading && config.underwater(h) {
+                let depth = ((config.sea_level - h) / config.sea_level.max(f64::EPSILON)).clamp(0.0, 1.0);
+                color = lerp_color(config.shallow_color, config.deep_color, depth as f32);
+            }
+            if config.texture_amount > 0.0 {
+                // Per block, so blocky maps keep solid blocks
+                let block = config.pixel_size.max(1) as usize;
+                let noise = pixel_hash(x / block, y / block, maps.seed) * 2.0 - 1.0;
+                color = shade_color(color, 1.0 + noise * config.texture_amount * TEXTURE_RANGE);
+            }
+            // Regions only cover land, so the coastline still reads
+            if let Some(regions) = regions.as_ref().filter(|_| !config.underwater(h)) {
+                color = lerp_color(color, region_color(regions[index]), config.region_opacity as f32);
+            }
+            if config.rivers && !config.underwater(h) && maps.flow(index) >= config.river_threshold {
+                color = config.river_color;
+            }
+            if config.hillshade {
+                let normal = maps.normal(x, y, 1.0);
+                let lit = normal[0] * light[0] + normal[1] * light[1] + normal[2] * light[2];
+                color = shade_color(color, 1.0 + config.shading_strength * (lit.max(0.0) - flat));
+            }
+            if config.contours && maps.crosses_contour(x, y, config.contour_interval) {
+                color = config.contour_color;
+            }
+            if let Some(regions) = regions.as_ref().filter(|_| config.region_borders && !config.underwater(h)) {
+                let [width, height] = maps.size;
+                let border = (x + 1 < width && regions[index + 1] != regions[index]) || (y + 1 < height && regions[index + width] != regions[index]);
+                if border {
+                    color = config.region_border_color;
+                }
+            }
+            if config.coastline && maps.on_coast(x, y, config, config.coastline_width.max(1) as usize) {
+                color = config.coastline_color;
+            }
+            color
+        })
+        .collect();
+    egui::ColorImage { size: maps.size, pixels }
+}
+
+// Average `factor`×`factor` blocks of pixels per channel
+fn downsample_image(image: &egui::ColorImage, factor: usize) -> egui::ColorImage {
+    let size = [image.size[0] / factor, image.size[1] / factor];
+    let count = (factor * factor) as u32;
+    let pixels = (0..size[0] * size[1])
+        .into_par_iter()
+        .map(|index| {
+            let (x, y) = (index % size[0] * factor, index / size[0] * factor);
+            let mut sum = [0u32; 4];
+            for i in 0..factor * factor {
+                let pixel = image.pixels[(y + i / factor) * image.size[0] + x + i % factor].to_array();
+                for (total, channel) in sum.iter_mut().zip(pixel) {
+                    *total += channel as u32;
+                }
+            }
+            let [r, g, b, a] = sum.map(|total| ((total + count / 2) / count) as u8);
+            Color32::from_rgba_premultiplied(r, g, b, a)
+        })
+        .collect();
+    egui::ColorImage { size, pixels }
+}
+
+// Apply brightness, contrast and gamma to every channel through a lookup table, so it's cheap enough to
+// redo on an already colored image every frame a slider is dragged
+pub fn adjust_image(config: &TerrainConfig, image: &mut egui::ColorImage) {
+    if config.brightness == 0.0 && config.contrast == 1.0 && config.gamma == 1.0 {
+        return;
+    }
+    let table: Vec<u8> = (0..=255u8)
+        .map(|channel| {
+            let value = ((channel as f64 / 255.0 - 0.5) * config.contrast + 0.5 + config.brightness).clamp(0.0, 1.0);
+            (value.powf(1.0 / config.gamma.max(0.01)) * 255.0).round() as u8
+        })
+        .collect();
+    for pixel in &mut image.pixels {
+        let [r, g, b, a] = pixel.to_array();
+        *pixel = Color32::from_rgba_premultiplied(table[r as usize], table[g as usize], table[b as usize], a);
+    }
+}
+
+// Unit vector pointing at the light; x is east and y is south, matching image coordinates
+fn light_direction(azimuth: f64, altitude: f64) -> [f64; 3] {
+    let (azimuth, altitude) = (azimuth.to_radians(), altitude.to_radians());
+    [
+        azimuth.sin() * altitude.cos(),
+        -azimuth.cos() * altitude.cos(),
+        altitude.sin(),
+    ]
+}
+
+// Blend per sRGB channel, `t` = 0 giving `from` and 1 giving `to`
+fn lerp_color(from: Color32, to: Color32, t: f32) -> Color32 {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color32::from_rgb(lerp(from.r(), to.r()), lerp(from.g(), to.g()), lerp(from.b(), to.b()))
+}
+
+// Cheap, well-mixed value in 0..1 for a pixel position and seed, finished with the lowbias32 mixer
+fn pixel_hash(x: usize, y: usize, seed: u32) -> f64 {
+    let mut h = (x as u32).wrapping_mul(0x9E37_79B9) ^ (y as u32).wrapping_mul(0x85EB_CA6B) ^ seed;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7FEB_352D);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846C_A68B);
+    h ^= h >> 16;
+    h as f64 / u32::MAX as f64
+}
+
+fn shade_color(color: Color32, factor: f64) -> Color32 {
+    let shade = |v: u8| (v as f64 * factor).round().clamp(0.0, 255.0) as u8;
+    Color32::from_rgb(shade(color.r()), shade(color.g()), shade(color.b()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> TerrainConfig {
+        TerrainConfig { width: 96, height: 64, ..TerrainConfig::default() }
+    }
+
+    // Largest height difference between horizontally or vertically adjacent pixels
+    fn largest_step(maps: &TerrainMaps) -> f64 {
+        let [width, height] = maps.size;
+        let at = |x: usize, y: usize| maps.heights[y * width + x];
+        let across = (0..height).flat_map(|y| (1..width).map(move |x| (at(x, y) - at(x - 1, y)).abs()));
+        let down = (1..height).flat_map(|y| (0..width).map(move |x| (at(x, y) - at(x, y - 1)).abs()));
+        across.chain(down).fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn tileable_noise_wraps_at_the_edges() {
+        for noise_kind in NoiseKind::ALL {
+            let config = TerrainConfig { tileable: true, warp_strength: 0.5, noise_kind, ..small_config() };
+            let sampler = HeightSampler::new(&config, 7, config.continent_scale);
+            let (width, height) = (config.width as f64, config.height as f64);
+            for y in [0.0, 13.5, 40.0] {
+                assert!((sampler.fbm(width, y) - sampler.fbm(0.0, y)).abs() < 1e-9, "{} doesn't wrap across", noise_kind.label());
+            }
+            for x in [0.0, 21.5, 77.0] {
+                assert!((sampler.fbm(x, height) - sampler.fbm(x, 0.0)).abs() < 1e-9, "{} doesn't wrap down", noise_kind.label());
+            }
+        }
+    }
+
+    #[test]
+    fn tileable_map_edges_are_neighbours() {
+        let config = TerrainConfig { tileable: true, ..small_config() };
+        let maps = generate_terrain(&config, 7).maps;
+        let [width, height] = maps.size;
+        let at = |x: usize, y: usize| maps.heights[y * width + x];
+        let step = largest_step(&maps);
+        for y in 0..height {
+            assert!((at(0, y) - at(width - 1, y)).abs() <= step, "row {} doesn't wrap", y);
+        }
+        for x in 0..width {
+            assert!((at(x, 0) - at(x, height - 1)).abs() <= step, "column {} doesn't wrap", x);
+        }
+    }
+
+    fn band_name(config: &TerrainConfig, height: f64) -> &str {
+        &config.biome_at(height).expect("the default bands aren't empty").name
+    }
+
+    // The next float below `value`, for checking the far side of a boundary
+    fn just_below(value: f64) -> f64 {
+        f64::from_bits(value.to_bits() - 1)
+    }
+
+    #[test]
+    fn band_boundaries_belong_to_the_band_above() {
+        // Sea level on the Water/Sand boundary, so no band is swapped for flooding or draining
+        let config = TerrainConfig { sea_level: 0.4, ..TerrainConfig::default() };
+        let expected = [(0.3, "Deep Water", "Water"), (0.4, "Water", "Sand"), (0.5, "Sand", "Grass"), (0.7, "Grass", "Mountain"), (0.8, "Mountain", "Snow")];
+        for (threshold, below, above) in expected {
+            assert_eq!(band_name(&config, threshold), above, "at {}", threshold);
+            assert_eq!(band_name(&config, just_below(threshold)), below, "just below {}", threshold);
+        }
+        assert_eq!(band_name(&config, 0.0), "Deep Water");
+        assert_eq!(band_name(&config, 1.0), "Snow");
+    }
+
+    #[test]
+    fn sea_level_is_land() {
+        // Inside the Sand band, so the water side has to swap it for the shallowest water band
+        let config = TerrainConfig { sea_level: 0.45, ..TerrainConfig::default() };
+        assert_eq!(band_name(&config, 0.45), "Sand");
+        assert_eq!(band_name(&config, just_below(0.45)), "Water");
+    }
+
+    #[test]
+    fn one_seed_gives_identical_output() {
+        let config = TerrainConfig {
+            color_mode: ColorMode::Biomes,
+            warp_strength: 0.6,
+            detail_weight: 0.4,
+            noise_kind: NoiseKind::Worley,
+            thermal_erosion: true,
+            thermal_iterations: 20,
+            rivers: true,
+            ..small_config()
+        };
+        let first = generate_terrain(&config, 1234);
+        let second = generate_terrain(&config, 1234);
+        assert!(first.image.pixels == second.image.pixels);
+        assert!(first.maps.heights == second.maps.heights && first.maps.moisture == second.maps.moisture);
+        assert!(!first.maps.moisture.is_empty() && !first.maps.flow.is_empty());
+    }
+
+    #[test]
+    fn one_seed_gives_identical_decorations() {
+        let config = TerrainConfig { droplets: 3_000, ..small_config() };
+        let run = || {
+            let maps = generate_terrain(&config, 99).maps;
+            let eroded = erode_terrain(&config, maps, 99, &Progress::default(), |_| {}).expect("a job outside any epoch never goes stale");
+            let points = crate::scatter::scatter(&config, &eroded.maps, "Grass", 6.0);
+            let regions = Regions::new(config.region_count as usize, eroded.maps.size, 99u32.wrapping_add(REGION_SEED_OFFSET)).assign(eroded.maps.size, 1);
+            (eroded.maps.heights, points, regions)
+        };
+        let (first, second) = (run(), run());
+        assert!(first.0 == second.0, "hydraulic erosion differs");
+        assert!(!first.1.is_empty() && first.1 == second.1, "scatter differs");
+        assert!(first.2 == second.2, "regions differ");
+    }
+
+    // Generate on a pool of `threads` threads, timing it
+    fn generate_on_threads(config: &TerrainConfig, threads: usize) -> (GeneratedTerrain, std::time::Duration) {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().expect("a pool of that size can be built");
+        let started = std::time::Instant::now();
+        let terrain = pool.install(|| generate_terrain(config, 5));
+        (terrain, started.elapsed())
+    }
+
+    // At least a few threads, so the rows are really split up even on a single-core machine
+    #[test]
+    fn parallel_generation_matches_sequential() {
+        let config = TerrainConfig { supersample: 2, ..small_config() };
+        let (sequential, _) = generate_on_threads(&config, 1);
+        let (parallel, _) = generate_on_threads(&config, rayon::current_num_threads().max(4));
+        assert!(sequential.maps.heights == parallel.maps.heights);
+        assert!(sequential.image.pixels == parallel.image.pixels);
+    }
+
+    // Timing comparison rather than a check, so it only runs on request:
+    // cargo test --release parallel_generation_timing -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn parallel_generation_timing() {
+        let config = TerrainConfig { width: 1024, height: 1024, ..TerrainConfig::default() };
+        let (sequential, sequential_time) = generate_on_threads(&config, 1);
+        let (parallel, parallel_time) = generate_on_threads(&config, rayon::current_num_threads());
+        eprintln!("1 thread: {:?}, {} threads: {:?}", sequential_time, rayon::current_num_threads(), parallel_time);
+        assert!(sequential.image.pixels == parallel.image.pixels);
+    }
+
+    #[test]
+    fn sea_level_above_the_snow_line_floods_everything() {
+        let config = TerrainConfig { sea_level: 0.85, ..TerrainConfig::default() };
+        for height in [0.0, 0.5, 0.84, 0.85, 0.95, 1.0] {
+            assert!(config.biome_at(height).is_some_and(|band| band.water), "{} isn't flooded", height);
+        }
+        let config = TerrainConfig { sea_level: 0.79, ..TerrainConfig::default() };
+        assert_eq!(band_name(&config, 0.95), "Snow");
+    }
+
+    #[test]
+    fn ridged_noise_with_no_weight_stays_finite() {
+        let config = TerrainConfig { ridged: true, octave_weights: vec![0.0; 6], ..small_config() };
+        assert!(generate_terrain(&config, 1).maps.heights.iter().all(|height| height.is_finite()));
+    }
+}